@@ -1,12 +1,37 @@
-use std::{process::exit, time::Duration};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::PathBuf,
+    process::exit,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "mqtt")]
+use std::{io::Read, net::TcpStream};
 
 use clap::{Arg, ArgAction, Command};
 use hyper_headset::{
-    devices::{connect_compatible_device, DeviceError, DeviceEvent, DeviceProperties, Headset},
-    VERBOSE,
+    config::{parse_settings, Config},
+    devices::{
+        connect_compatible_device, connect_device_by_serial, hid_log, list_compatible_devices,
+        list_hid_interfaces, side_tone_volume_from_percent, ChargingStatus, DeviceError,
+        DeviceEvent, DeviceProperties, Headset, NUM_BANDS,
+    },
+    info_println, QUIET, VERBOSE,
 };
 
 const SHOW_ALL_OPTIONS: bool = false;
+/// Minimum time between two active refreshes while serving metrics.
+/// Querying the device too frequently can lead to instability.
+const METRICS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// Clients that do not send their request within this time are dropped
+const METRICS_READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time between two attempts to open the device again
+#[cfg(feature = "mqtt")]
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// Keep alive in seconds, longer than METRICS_REFRESH_INTERVAL so one ping per refresh is enough
+#[cfg(feature = "mqtt")]
+const MQTT_KEEP_ALIVE: u16 = 60;
 
 /// helper function to enable help messages
 fn device_supports<F>(device: &Result<Headset, DeviceError>, f: F) -> bool
@@ -19,8 +44,333 @@ where
         .unwrap_or(false)
 }
 
+/// Opens the device selected with `--device` again, e.g. after the dongle was replugged
+fn reconnect(serial_number: Option<&str>) -> Option<Headset> {
+    let device = match serial_number {
+        Some(serial_number) => connect_device_by_serial(serial_number),
+        None => connect_compatible_device(),
+    };
+    device
+        .map_err(|e| eprintln!("Connecting failed with error: {e}"))
+        .ok()
+}
+
+/// Actively refreshes the device state.
+/// The device is dropped when it is gone and opened again on the next call.
+fn refresh(device: &mut Option<Headset>, serial_number: Option<&str>) {
+    if device.is_none() {
+        *device = reconnect(serial_number);
+    }
+    let Some(headset) = device else {
+        return;
+    };
+    match headset.active_refresh_state() {
+        Ok(()) => (),
+        // the dongle is still present, only the headset is off
+        Err(DeviceError::HeadSetOff()) => (),
+        Err(error) => {
+            eprintln!("{error}");
+            *device = None;
+        }
+    }
+}
+
+/// Formats the device properties in the Prometheus text exposition format
+fn format_metrics(properties: &DeviceProperties) -> String {
+    let device = properties
+        .device_name
+        .as_deref()
+        .unwrap_or("Unknown")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    let charging = properties.charging.and_then(|charging| match charging {
+        ChargingStatus::NotCharging => Some(0),
+        ChargingStatus::Charging => Some(1),
+        ChargingStatus::FullyCharged => Some(2),
+        ChargingStatus::Unknown(_) => None,
+    });
+    let metrics = [
+        (
+            "hyperheadset_battery_percent",
+            "Battery level in percent.",
+            properties.battery_level.map(|level| level as u64),
+        ),
+        (
+            "hyperheadset_charging",
+            "0 = not charging, 1 = charging, 2 = fully charged.",
+            charging,
+        ),
+        (
+            "hyperheadset_connected",
+            "Whether the headset is connected to the dongle.",
+            properties.connected.map(|connected| connected as u64),
+        ),
+        (
+            "hyperheadset_muted",
+            "Whether the microphone is muted.",
+            properties.muted.map(|muted| muted as u64),
+        ),
+    ];
+    let mut output = String::new();
+    for (name, help, value) in metrics {
+        let Some(value) = value else {
+            continue;
+        };
+        output += &format!(
+            "# HELP {name} {help}\n# TYPE {name} gauge\n{name}{{device=\"{device}\"}} {value}\n"
+        );
+    }
+    output
+}
+
+/// Serves the device state on /metrics until the process is killed.
+/// The device is opened again if it disappears, e.g. after the dongle was replugged.
+fn serve_metrics(device: Headset, address: &str, port: u16, serial_number: Option<&str>) -> ! {
+    let listener = match TcpListener::bind((address, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {address}:{port}: {e}");
+            exit(1);
+        }
+    };
+    let mut device = Some(device);
+    let mut last_refresh: Option<Instant> = None;
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if stream.set_read_timeout(Some(METRICS_READ_TIMEOUT)).is_err() {
+            continue;
+        }
+        let mut request_line = String::new();
+        if BufReader::new(&stream)
+            .read_line(&mut request_line)
+            .is_err()
+        {
+            continue;
+        }
+        let response = if request_line.starts_with("GET /metrics ") {
+            if device.is_none()
+                || last_refresh.is_none_or(|last| last.elapsed() >= METRICS_REFRESH_INTERVAL)
+            {
+                refresh(&mut device, serial_number);
+                last_refresh = Some(Instant::now());
+            }
+            // without a device no metrics are exported, so the series go stale
+            let body = device
+                .as_ref()
+                .map(|device| format_metrics(&device.device_properties()))
+                .unwrap_or_default();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+        };
+        let _ = stream.write_all(response.as_bytes());
+    }
+    exit(1);
+}
+
+#[cfg(feature = "mqtt")]
+/// Prefixes an MQTT packet with its fixed header and variable length encoded size.
+fn mqtt_packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+#[cfg(feature = "mqtt")]
+fn mqtt_string(value: &str) -> Vec<u8> {
+    let mut bytes = (value.len() as u16).to_be_bytes().to_vec();
+    bytes.extend_from_slice(value.as_bytes());
+    bytes
+}
+
+#[cfg(feature = "mqtt")]
+/// Opens an MQTT 3.1.1 session with a keep alive of MQTT_KEEP_ALIVE seconds.
+fn mqtt_connect(broker: &str, client_id: &str) -> std::io::Result<TcpStream> {
+    let address = broker.strip_prefix("tcp://").unwrap_or(broker);
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:1883")
+    };
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut body = mqtt_string("MQTT");
+    // protocol level 4, clean session
+    body.extend_from_slice(&[4, 0x02]);
+    body.extend_from_slice(&MQTT_KEEP_ALIVE.to_be_bytes());
+    body.extend(mqtt_string(client_id));
+    stream.write_all(&mqtt_packet(0x10, &body))?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack)?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(std::io::Error::other(format!(
+            "broker refused the connection with code {}",
+            connack[3]
+        )));
+    }
+    Ok(stream)
+}
+
+#[cfg(feature = "mqtt")]
+/// Publishes a retained message with QoS 0.
+fn mqtt_publish(stream: &mut TcpStream, topic: &str, payload: &str) -> std::io::Result<()> {
+    let mut body = mqtt_string(topic);
+    body.extend_from_slice(payload.as_bytes());
+    stream.write_all(&mqtt_packet(0x31, &body))
+}
+
+#[cfg(feature = "mqtt")]
+/// Keeps the session alive while nothing changes and checks that the broker still answers.
+fn mqtt_ping(stream: &mut TcpStream) -> std::io::Result<()> {
+    stream.write_all(&mqtt_packet(0xC0, &[]))?;
+    let mut pingresp = [0u8; 2];
+    stream.read_exact(&mut pingresp)?;
+    if pingresp[0] != 0xD0 {
+        return Err(std::io::Error::other("broker did not answer the ping"));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "mqtt")]
+/// The state topics below `hyperheadset/<id>/` and their values.
+fn mqtt_states(properties: &DeviceProperties) -> Vec<(&'static str, String)> {
+    let charging = properties
+        .charging
+        .map(|charging| matches!(charging, ChargingStatus::Charging));
+    [
+        ("battery", properties.battery_level.map(|l| l.to_string())),
+        ("charging", charging.map(|c| c.to_string())),
+        ("connected", properties.connected.map(|c| c.to_string())),
+        ("muted", properties.muted.map(|m| m.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value?)))
+    .collect()
+}
+
+#[cfg(feature = "mqtt")]
+/// Home Assistant MQTT discovery messages so the entities are created automatically.
+fn mqtt_discovery(id: &str, properties: &DeviceProperties) -> Vec<(String, String)> {
+    let device_name = hyper_headset::devices::json_string(
+        properties
+            .device_name
+            .as_deref()
+            .unwrap_or("HyperX headset"),
+    );
+    let device = format!(
+        "{{\"identifiers\": [\"hyperheadset_{id}\"], \"name\": {device_name}, \"manufacturer\": \"HyperX\"}}"
+    );
+    [
+        ("sensor", "battery", "Battery", "\"device_class\": \"battery\", \"unit_of_measurement\": \"%\""),
+        ("binary_sensor", "charging", "Charging", "\"device_class\": \"battery_charging\", \"payload_on\": \"true\", \"payload_off\": \"false\""),
+        ("binary_sensor", "connected", "Connected", "\"device_class\": \"connectivity\", \"payload_on\": \"true\", \"payload_off\": \"false\""),
+        ("binary_sensor", "muted", "Microphone muted", "\"payload_on\": \"true\", \"payload_off\": \"false\""),
+    ]
+    .into_iter()
+    .map(|(component, name, pretty_name, extra)| {
+        (
+            format!("homeassistant/{component}/hyperheadset_{id}/{name}/config"),
+            format!(
+                "{{\"name\": \"{pretty_name}\", \"unique_id\": \"hyperheadset_{id}_{name}\", \"state_topic\": \"hyperheadset/{id}/{name}\", {extra}, \"device\": {device}}}"
+            ),
+        )
+    })
+    .collect()
+}
+
+#[cfg(feature = "mqtt")]
+/// Publishes the headset state to an MQTT broker whenever it changes.
+/// The broker connection and the device are reestablished independently if they are lost.
+fn serve_mqtt(device: Headset, broker: &str, serial_number: Option<&str>) -> ! {
+    let properties = device.device_properties();
+    let id: String = match &properties.serial_number {
+        Some(serial_number) => serial_number
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect(),
+        None => format!("{:04x}", properties.product_id),
+    };
+    let mut device = Some(device);
+    let mut connection: Option<TcpStream> = None;
+    let mut published = Vec::new();
+    loop {
+        refresh(&mut device, serial_number);
+        let Some(properties) = device.as_ref().map(Headset::device_properties) else {
+            std::thread::sleep(RECONNECT_INTERVAL);
+            continue;
+        };
+        if connection.is_none() {
+            match mqtt_connect(broker, &format!("hyperheadset_{id}")) {
+                Ok(mut stream) => {
+                    let discovery = mqtt_discovery(&id, &properties);
+                    match discovery
+                        .iter()
+                        .try_for_each(|(topic, payload)| mqtt_publish(&mut stream, topic, payload))
+                    {
+                        Ok(()) => {
+                            published.clear();
+                            connection = Some(stream);
+                        }
+                        Err(e) => eprintln!("Failed to publish to the MQTT broker: {e}"),
+                    }
+                }
+                Err(e) => eprintln!("Failed to connect to the MQTT broker {broker}: {e}"),
+            }
+        }
+        if let Some(stream) = &mut connection {
+            let states = mqtt_states(&properties);
+            let changed: Vec<_> = states
+                .iter()
+                .filter(|state| !published.contains(*state))
+                .collect();
+            let result = if changed.is_empty() {
+                mqtt_ping(stream)
+            } else {
+                changed.into_iter().try_for_each(|(name, value)| {
+                    mqtt_publish(stream, &format!("hyperheadset/{id}/{name}"), value)
+                })
+            };
+            match result {
+                Ok(()) => published = states,
+                Err(e) => {
+                    eprintln!("Failed to publish to the MQTT broker: {e}");
+                    connection = None;
+                }
+            }
+        }
+        std::thread::sleep(METRICS_REFRESH_INTERVAL);
+    }
+}
+
 fn create_command(device: &Result<Headset, DeviceError>) -> Command {
-    Command::new(env!("CARGO_PKG_NAME"))
+    let mut automatic_shutdown_help =
+        "Set the delay in minutes after which the headset will automatically shutdown.\n0 will disable automatic shutdown.".to_string();
+    if let Some(options) = device
+        .as_ref()
+        .ok()
+        .and_then(|headset| headset.device_properties().automatic_shutdown_options)
+    {
+        let options: Vec<String> = options.iter().map(|option| option.to_string()).collect();
+        automatic_shutdown_help += &format!(" This headset only accepts {}.", options.join(", "));
+    }
+    let command = Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .disable_version_flag(false)
         .disable_help_flag(true)
@@ -31,11 +381,9 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
             Arg::new("automatic_shutdown")
                 .long("automatic_shutdown")
                 .required(false)
-                .help(
-                    "Set the delay in minutes after which the headset will automatically shutdown.\n0 will disable automatic shutdown.",
-                )
-                    .hide(!SHOW_ALL_OPTIONS
-                        && !device_supports(device, |d| d.can_set_automatic_shutdown))
+                .help(automatic_shutdown_help)
+                .hide(!SHOW_ALL_OPTIONS
+                        && !device_supports(device, |d| d.capabilities.can_set_automatic_shutdown))
                 .value_parser(clap::value_parser!(u8)),
         )
         .arg(
@@ -44,26 +392,50 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .required(false)
                 .help("Mute or unmute the headset.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_mute))
+                    && !device_supports(device, |d| d.capabilities.can_set_mute))
                 .value_parser(clap::value_parser!(bool)),
         )
+        .arg(
+            Arg::new("toggle_mute")
+                .long("toggle_mute")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .conflicts_with("mute")
+                .help("Mute the headset if it is unmuted and vice versa.")
+                .hide(!SHOW_ALL_OPTIONS
+                    && !device_supports(device, |d| d.capabilities.can_set_mute)),
+        )
         .arg(
             Arg::new("enable_side_tone")
                 .long("enable_side_tone")
                 .required(false)
                 .help("Enable or disable side tone.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_side_tone))
+                    && !device_supports(device, |d| d.capabilities.can_set_side_tone))
                 .value_parser(clap::value_parser!(bool)),
         )
         .arg(
             Arg::new("side_tone_volume")
                 .long("side_tone_volume")
                 .required(false)
-                .help("Set the side tone volume.")
+                .help("Set the side tone volume. Some devices use a signed range, e.g. -5 to 5.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_side_tone_volume))
-                .value_parser(clap::value_parser!(u8)),
+                    && !device_supports(device, |d| d.capabilities.can_set_side_tone_volume))
+                .allow_negative_numbers(true)
+                .value_parser(clap::value_parser!(i16).range(-128..=255)),
+        )
+        .arg(
+            Arg::new("side_tone_volume_percent")
+                .long("side_tone_volume_percent")
+                .required(false)
+                .help("Set the side tone volume from 0 to 100%, mapped to the range of the device.")
+                .hide(!SHOW_ALL_OPTIONS
+                    && !device_supports(device, |d| {
+                        d.capabilities.can_set_side_tone_volume
+                            && d.side_tone_volume_range.is_some()
+                    }))
+                .conflicts_with("side_tone_volume")
+                .value_parser(clap::value_parser!(u8).range(0..=100)),
         )
         .arg(
             Arg::new("enable_voice_prompt")
@@ -71,7 +443,7 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .required(false)
                 .help("Enable voice prompt. This may not be supported on your device.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_voice_prompt))
+                    && !device_supports(device, |d| d.capabilities.can_set_voice_prompt))
                 .value_parser(clap::value_parser!(bool)),
         )
         .arg(
@@ -80,7 +452,7 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .required(false)
                 .help("Enables surround sound. This may be on by default and cannot be changed on your device.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_surround_sound))
+                    && !device_supports(device, |d| d.capabilities.can_set_surround_sound))
                 .value_parser(clap::value_parser!(bool)),
         )
         .arg(
@@ -89,7 +461,7 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .required(false)
                 .help("Mute or unmute playback.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_silent_mode))
+                    && !device_supports(device, |d| d.capabilities.can_set_silent_mode))
                 .value_parser(clap::value_parser!(bool)),
         )
         .arg(
@@ -98,9 +470,113 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .required(false)
                 .help("Activates noise gate.")
                 .hide(!SHOW_ALL_OPTIONS
-                    && !device_supports(device, |d| d.can_set_silent_mode))
+                    && !device_supports(device, |d| d.capabilities.can_set_noise_gate))
                 .value_parser(clap::value_parser!(bool)),
         )
+        .arg(
+            Arg::new("eq_flat")
+                .long("eq_flat")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("Reset all equalizer bands to 0 dB.")
+                .hide(!SHOW_ALL_OPTIONS
+                    && !device_supports(device, |d| d.capabilities.can_set_equalizer)),
+        )
+        .arg(
+            Arg::new("eq_slot")
+                .long("eq_slot")
+                .required(false)
+                .help("Select an equalizer slot stored on the headset.")
+                .hide(!SHOW_ALL_OPTIONS
+                    && !device_supports(device, |d| d.capabilities.can_set_active_eq_slot))
+                .value_parser(clap::value_parser!(u8)),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .required(false)
+                .help("Apply all settings of a [profile.<name>] table in config.toml. It overrides --config_file, all other options take precedence.")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("config_file")
+                .long("config_file")
+                .required(false)
+                .help("Apply all settings in this file, it uses the keys of a profile. Settings the headset does not support are skipped. --profile and all other options take precedence.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("battery")
+                .long("battery")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("Only print the battery level. This is faster than querying everything."),
+        )
+        .arg(
+            Arg::new("version_verbose")
+                .long("version_verbose")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("Print the version together with the product name, serial number, and dongle USB release of the connected device"),
+        )
+        .arg(
+            Arg::new("selftest")
+                .long("selftest")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("Send every query once and print which ones the headset answered. Attach this to bug reports."),
+        )
+        .arg(
+            Arg::new("list_devices")
+                .long("list_devices")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("List all attached compatible devices"),
+        )
+        .arg(
+            Arg::new("dump_hid")
+                .long("dump_hid")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("Print the HID interfaces of all attached HyperX and HP devices. Attach this to requests for new headsets."),
+        )
+        .arg(
+            Arg::new("record_hid")
+                .long("record_hid")
+                .required(false)
+                .help("Record all HID traffic with timestamps to this .hidlog file")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("replay_hid")
+                .long("replay_hid")
+                .required(false)
+                .help("Print the events decoded from the reports in a .hidlog file. No headset is needed.")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("device")
+                .long("device")
+                .required(false)
+                .help("Only connect to the device with this serial number")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("metrics_port")
+                .long("metrics_port")
+                .required(false)
+                .help("Serve the headset state in the Prometheus format on this port under /metrics")
+                .value_parser(clap::value_parser!(u16)),
+        )
+        .arg(
+            Arg::new("metrics_address")
+                .long("metrics_address")
+                .required(false)
+                .default_value("127.0.0.1")
+                .requires("metrics_port")
+                .help("Address the metrics are served on, use 0.0.0.0 to make them reachable from other machines")
+                .value_parser(clap::value_parser!(String)),
+        )
         .arg(
             Arg::new("verbose")
                 .long("verbose")
@@ -109,6 +585,15 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .required(false)
                 .help("Use verbose output"),
         )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .conflicts_with("verbose")
+                .help("Only print errors and the requested output"),
+        )
         .arg(
             Arg::new("help")
                 .long("help")
@@ -123,7 +608,17 @@ fn create_command(device: &Result<Headset, DeviceError>) -> Command {
                 .action(ArgAction::SetTrue)
                 .required(false)
                 .help("Use JSON output. Time is in seconds."),
-        )
+        );
+    #[cfg(feature = "mqtt")]
+    let command = command.arg(
+        Arg::new("mqtt_broker")
+            .long("mqtt_broker")
+            .required(false)
+            .help("Publish the headset state to this MQTT broker, e.g. tcp://localhost:1883, with Home Assistant discovery")
+            .conflicts_with("metrics_port")
+            .value_parser(clap::value_parser!(String)),
+    );
+    command
 }
 
 fn main() {
@@ -150,8 +645,68 @@ fn main() {
     let command = create_command(&device);
     let matches = command.get_matches();
     VERBOSE.set(matches.get_flag("verbose")).unwrap();
+    QUIET.set(matches.get_flag("quiet")).unwrap();
+
+    if matches.get_flag("list_devices") {
+        match list_compatible_devices() {
+            Ok(devices) if devices.is_empty() => println!("No compatible device found"),
+            Ok(devices) => devices.iter().for_each(|device| println!("{device}")),
+            Err(e) => {
+                eprintln!("{e}");
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if matches.get_flag("dump_hid") {
+        match list_hid_interfaces() {
+            Ok(interfaces) if interfaces.is_empty() => println!("No HyperX or HP device found"),
+            Ok(interfaces) => interfaces
+                .iter()
+                .for_each(|interface| println!("{interface}")),
+            Err(e) => {
+                eprintln!("{e}");
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("replay_hid") {
+        match hid_log::replay(path) {
+            Ok(reports) => {
+                for report in reports {
+                    let events = match report.events {
+                        Some(events) => events
+                            .iter()
+                            .map(|event| event.to_json())
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        None => "unknown".to_string(),
+                    };
+                    println!("{} {:02X?}: {events}", report.time, report.report);
+                }
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                exit(1);
+            }
+        }
+        exit(0);
+    }
 
-    let device = connect_compatible_device();
+    if let Some(path) = matches.get_one::<PathBuf>("record_hid") {
+        if let Err(e) = hid_log::start_recording(path) {
+            eprintln!("Failed to record to {}: {e}", path.display());
+            exit(1);
+        }
+    }
+
+    let device = match matches.get_one::<String>("device") {
+        Some(serial_number) => connect_device_by_serial(serial_number),
+        None => connect_compatible_device(),
+    };
 
     // print help with headset specific options
     if matches.get_flag("help") {
@@ -160,6 +715,31 @@ fn main() {
         exit(0);
     }
 
+    if matches.get_flag("version_verbose") {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        match &device {
+            Ok(device) => {
+                let properties = device.device_properties();
+                let unknown = || "Unknown".to_string();
+                println!("Driver:             {}", device.model_name());
+                println!(
+                    "Product:            {}",
+                    properties.device_name.unwrap_or_else(unknown)
+                );
+                println!(
+                    "Serial number:      {}",
+                    properties.serial_number.unwrap_or_else(unknown)
+                );
+                println!(
+                    "Dongle USB release: {}",
+                    properties.dongle_usb_release.unwrap_or_else(unknown)
+                );
+            }
+            Err(e) => println!("{e}"),
+        }
+        exit(0);
+    }
+
     let mut device = match device {
         Ok(device) => device,
         Err(e) => {
@@ -168,7 +748,108 @@ fn main() {
         }
     };
 
+    if matches.get_flag("selftest") {
+        let properties = device.device_properties();
+        let unknown = || "Unknown".to_string();
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        println!("Driver: {}", device.model_name());
+        println!(
+            "Device: vendorID: 0x{:04X} productID: 0x{:04X} name: {}",
+            properties.vendor_id,
+            properties.product_id,
+            properties.device_name.unwrap_or_else(unknown)
+        );
+        println!(
+            "Dongle USB release: {}",
+            properties.dongle_usb_release.unwrap_or_else(unknown)
+        );
+        match device.self_test() {
+            Ok(report) => {
+                for (name, events) in report {
+                    match events {
+                        Some(events) => {
+                            let events: Vec<String> =
+                                events.iter().map(|event| event.to_json()).collect();
+                            println!("{name}: OK {}", events.join(", "));
+                        }
+                        None => println!("{name}: no response or unknown response"),
+                    }
+                }
+            }
+            Err(error) => {
+                println!("Self-test failed: {error}");
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if matches.get_flag("battery") {
+        match device.quick_battery() {
+            Ok(level) => {
+                if matches.get_flag("json") {
+                    let level = level.map_or("null".to_string(), |level| level.to_string());
+                    println!("{{\n  \"battery_level\": {level}\n}}");
+                } else {
+                    let level = level.map_or("Unknown".to_string(), |level| format!("{level}%"));
+                    println!("{level}");
+                }
+                exit(0);
+            }
+            Err(error) => {
+                eprintln!("{error}");
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(port) = matches.get_one::<u16>("metrics_port") {
+        serve_metrics(
+            device,
+            matches.get_one::<String>("metrics_address").unwrap(),
+            *port,
+            matches.get_one::<String>("device").map(String::as_str),
+        );
+    }
+
+    #[cfg(feature = "mqtt")]
+    if let Some(broker) = matches.get_one::<String>("mqtt_broker") {
+        serve_mqtt(
+            device,
+            broker,
+            matches.get_one::<String>("device").map(String::as_str),
+        );
+    }
+
+    // later commands win, so the order is --config_file, --profile, then the explicit options
     let mut commands = Vec::new();
+    if let Some(path) = matches.get_one::<PathBuf>("config_file") {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("ERROR: Failed to read {}: {e}", path.display());
+                exit(1);
+            }
+        };
+        let capabilities = device.device_properties().capabilities;
+        for (key, setting) in parse_settings(&content) {
+            if capabilities.supports(&setting) {
+                commands.push(setting);
+            } else {
+                info_println!("Skipping {key}, it is not supported by this device");
+            }
+        }
+    }
+
+    if let Some(name) = matches.get_one::<String>("profile") {
+        let config = Config::load();
+        let Some(profile) = config.profile(name) else {
+            eprintln!("ERROR: No profile named {name} in the config file");
+            exit(1);
+        };
+        commands.extend(profile.settings.iter().copied());
+    }
+
     if let Some(delay) = matches.get_one::<u8>("automatic_shutdown") {
         let delay = *delay as u64;
         commands.push(DeviceEvent::AutomaticShutdownAfter(Duration::from_secs(
@@ -180,12 +861,33 @@ fn main() {
         commands.push(DeviceEvent::Muted(*mute));
     }
 
+    if matches.get_flag("toggle_mute") {
+        if let Err(error) = device.active_refresh_state() {
+            eprintln!("{error}");
+            std::process::exit(1);
+        };
+        // mute if the current state is unknown
+        let muted = device.device_properties().muted.unwrap_or(false);
+        commands.push(DeviceEvent::Muted(!muted));
+    }
+
     if let Some(enable) = matches.get_one::<bool>("enable_side_tone") {
         commands.push(DeviceEvent::SideToneOn(*enable));
     }
 
-    if let Some(volume) = matches.get_one::<u8>("side_tone_volume") {
-        commands.push(DeviceEvent::SideToneVolume(*volume));
+    if let Some(volume) = matches.get_one::<i16>("side_tone_volume") {
+        // negative volumes are sent as two's complement
+        commands.push(DeviceEvent::SideToneVolume(*volume as u8));
+    }
+
+    if let Some(percent) = matches.get_one::<u8>("side_tone_volume_percent") {
+        let Some(range) = device.device_properties().side_tone_volume_range else {
+            eprintln!("ERROR: The side tone volume range of this device is unknown, use --side_tone_volume");
+            exit(1);
+        };
+        commands.push(DeviceEvent::SideToneVolume(side_tone_volume_from_percent(
+            *percent, &range,
+        )));
     }
 
     if let Some(enable) = matches.get_one::<bool>("enable_voice_prompt") {
@@ -204,14 +906,51 @@ fn main() {
         commands.push(DeviceEvent::NoiseGateActive(*activate));
     }
 
-    for command in commands {
-        if let Err(e) = device.try_apply(command) {
-            eprintln!("{e}");
-            std::process::exit(1);
+    if matches.get_flag("eq_flat") {
+        commands.push(DeviceEvent::Equalizer([0.0; NUM_BANDS]));
+    }
+
+    if let Some(slot) = matches.get_one::<u8>("eq_slot") {
+        commands.push(DeviceEvent::ActiveEqSlot(*slot));
+    }
+
+    // these settings only affect the microphone
+    let changes_mic_settings = commands.iter().any(|command| {
+        matches!(
+            command,
+            DeviceEvent::Muted(_)
+                | DeviceEvent::SideToneOn(_)
+                | DeviceEvent::SideToneVolume(_)
+                | DeviceEvent::NoiseGateActive(_)
+        )
+    });
+
+    // keep applying after a failure so one bad setting does not leave the others unapplied
+    let results: Vec<(&str, Result<(), String>)> = commands
+        .iter()
+        .map(|command| (setting_name(command), device.try_apply(*command)))
+        .collect();
+    let failed = results.iter().any(|(_, result)| result.is_err());
+    if failed || results.len() > 1 {
+        let table = results
+            .iter()
+            .map(|(name, result)| {
+                let result = match result {
+                    Ok(()) => "OK",
+                    Err(e) => e.as_str(),
+                };
+                format!("{name:<25}{result}")
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        if failed {
+            eprintln!("{table}");
+        } else {
+            info_println!("{table}");
         }
     }
 
-    std::thread::sleep(Duration::from_secs_f64(0.5));
+    std::thread::sleep(device.settle_delay());
 
     // setting an option may cause a response form the headset
     if device.allow_passive_refresh() {
@@ -226,34 +965,36 @@ fn main() {
         std::process::exit(1);
     };
 
+    // keep the JSON output parseable
+    if matches.get_flag("toggle_mute") && !matches.get_flag("json") {
+        match device.device_properties().muted {
+            Some(true) => println!("Headset is now muted"),
+            Some(false) => println!("Headset is now unmuted"),
+            None => println!("Mute state is unknown"),
+        }
+    }
+
+    // the headset does not report the equalizer, so the state output cannot show it
+    let equalizer_reset = results
+        .iter()
+        .any(|(name, result)| *name == "equalizer" && result.is_ok());
+    if matches.get_flag("eq_flat") && equalizer_reset && !matches.get_flag("json") {
+        println!("Equalizer reset to 0 dB");
+    }
+
+    if changes_mic_settings && device.device_properties().mic_connected == Some(false) {
+        eprintln!(
+            "WARNING: The microphone is not attached, microphone settings may have no effect."
+        );
+    }
+
     if let Some(output_json) = matches.get_one::<bool>("json") {
         if *output_json {
             let properties = device.device_properties();
             let mut headset_info_json = "{\n  ".to_string();
 
-            let json_properties: Vec<String> = properties
-                .get_properties()
-                .iter()
-                .filter_map(|property| match property {
-                    hyper_headset::devices::PropertyDescriptorWrapper::Int(
-                        property_descriptor,
-                        _items,
-                    ) => property_descriptor
-                        .data
-                        .map(|data| format!("\"{}\": {}", property_descriptor.name, data)),
-                    hyper_headset::devices::PropertyDescriptorWrapper::Bool(
-                        property_descriptor,
-                    ) => property_descriptor
-                        .data
-                        .map(|data| format!("\"{}\": {}", property_descriptor.name, data)),
-                    hyper_headset::devices::PropertyDescriptorWrapper::String(
-                        property_descriptor,
-                    ) => property_descriptor
-                        .data
-                        .as_ref()
-                        .map(|data| format!("\"{}\": \"{}\"", property_descriptor.name, data)),
-                })
-                .collect();
+            let mut json_properties = properties.to_json_fields();
+            json_properties.insert(0, format!("\"driver\": \"{}\"", device.model_name()));
 
             headset_info_json += &json_properties.join(",\n  ");
 
@@ -265,4 +1006,26 @@ fn main() {
     } else {
         println!("{}", device.device_properties());
     }
+
+    if failed {
+        exit(1);
+    }
+}
+
+/// The option that sets what `event` applies, used in the result table
+fn setting_name(event: &DeviceEvent) -> &'static str {
+    match event {
+        DeviceEvent::Muted(_) => "mute",
+        DeviceEvent::AutomaticShutdownAfter(_) => "automatic_shutdown",
+        DeviceEvent::SideToneOn(_) => "enable_side_tone",
+        DeviceEvent::SideToneVolume(_) => "side_tone_volume",
+        DeviceEvent::VoicePrompt(_) => "enable_voice_prompt",
+        DeviceEvent::SurroundSound(_) => "surround_sound",
+        DeviceEvent::Silent(_) => "mute_playback",
+        DeviceEvent::NoiseGateActive(_) => "activate_noise_gate",
+        DeviceEvent::Equalizer(_) => "equalizer",
+        DeviceEvent::LedState(_, _) | DeviceEvent::LedBrightness(_) => "led",
+        DeviceEvent::ActiveEqSlot(_) => "eq_slot",
+        _ => "unknown",
+    }
 }
@@ -1,166 +1,197 @@
-use hidapi::{DeviceInfo, HidApi};
+use std::io::{BufRead, Write};
+
+use clap::{Arg, ArgAction, Command};
+use hidapi::{HidApi, HidDevice};
 
 const VENDOR_IDS: [u16; 2] = [0x0951, 0x03F0];
 // Possible Cloud II Wireless product IDs
 const PRODUCT_IDS: [u16; 3] = [0x1718, 0x018B, 0x0b92];
+const RESPONSE_BUFFER_SIZE: usize = 64;
+/// How long to wait for the first response to a packet
+const RESPONSE_TIMEOUT_MS: i32 = 1000;
+/// How long to wait for further responses after the first one
+const FOLLOW_UP_TIMEOUT_MS: i32 = 100;
 
-const BASE_PACKET: [u8; 62] = {
-    let mut tmp = [0u8; 62];
-    tmp[0] = 0x06;
-    tmp[1] = 0x00;
-    tmp[2] = 0x02;
-    tmp[3] = 0x00;
-    tmp[4] = 0x9A;
-    tmp[5] = 0x00;
-    tmp[6] = 0x00;
-    tmp[7] = 0x68;
-    tmp[8] = 0x4A;
-    tmp[9] = 0x8E;
-    tmp[10] = 0x0A;
-    tmp[11] = 0x00;
-    tmp[12] = 0x00;
-    tmp[13] = 0x00;
-    tmp[14] = 0xBB;
-    tmp[15] = 0x01;
-    tmp[16] = 0x00;
-    tmp[17] = 0x00;
-    tmp[18] = 0x00;
-    tmp[19] = 0x00;
-    tmp[20] = 0x00;
-    tmp[21] = 0x00;
-    tmp[22] = 0x00;
-    tmp[23] = 0x00;
-    tmp[24] = 0x00;
-    tmp[25] = 0x00;
-    tmp[26] = 0x00;
-    tmp[27] = 0x00;
-    tmp[28] = 0x00;
-    tmp[29] = 0x00;
-    tmp[30] = 0x00;
-    tmp[31] = 0x00;
-    tmp[32] = 0x00;
-    tmp[33] = 0x00;
-    tmp[34] = 0x00;
-    tmp[35] = 0x00;
-    tmp[36] = 0x00;
-    tmp[37] = 0x00;
-    tmp[38] = 0x00;
-    tmp[39] = 0x00;
-    tmp[40] = 0x00;
-    tmp[41] = 0x00;
-    tmp[42] = 0x00;
-    tmp[43] = 0x00;
-    tmp[44] = 0x00;
-    tmp[45] = 0x00;
-    tmp[46] = 0x00;
-    tmp[47] = 0x00;
-    tmp[48] = 0x00;
-    tmp[49] = 0x00;
-    tmp[50] = 0x00;
-    tmp[51] = 0x00;
-    tmp[52] = 0x00;
-    tmp[53] = 0x00;
-    tmp[54] = 0x00;
-    tmp[55] = 0x00;
-    tmp[56] = 0x00;
-    tmp[57] = 0x00;
-    tmp[58] = 0x00;
-    tmp[59] = 0x00;
-    tmp[60] = 0x00;
-    tmp[61] = 0x00;
-    tmp
-};
+/// An opened HID interface of a tested device
+struct Interface {
+    name: String,
+    device: HidDevice,
+}
 
-const PACKET1: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[2] = 0;
-    tmp[4] = 0xFF;
-    tmp[10] = 0x00;
-    tmp[14] = 0x00;
-    tmp[15] = 0x00;
-    tmp
-};
-const PACKET2: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[15] = 0x11;
-    tmp
-};
-const PACKET3: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[15] = 0x1D;
-    tmp
-};
-const PACKET4: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[15] = 0x09;
-    tmp
-};
-const PACKET5: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[15] = 0x02;
-    tmp
-};
-const PACKET6: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[15] = 0x03;
-    tmp
-};
-const PACKET7: [u8; 62] = {
-    let mut tmp = BASE_PACKET;
-    tmp[15] = 0x1A;
-    tmp
-};
-const PACKET8: [u8; 62] = PACKET5;
-const PACKET9: [u8; 62] = PACKET6;
-const PACKET10: [u8; 62] = PACKET5;
-const PACKET11: [u8; 62] = PACKET6;
+fn main() {
+    let matches = Command::new("packet_tester")
+        .about("Sends raw packets to HyperX headsets and prints the responses.\nWithout --packet, packets are read from stdin, one per line.")
+        .arg(
+            Arg::new("packet")
+                .long("packet")
+                .required(false)
+                .help("Send this packet, e.g. 06:00:02:00:9A")
+                .value_parser(parse_packet),
+        )
+        .arg(
+            Arg::new("loop")
+                .long("loop")
+                .action(ArgAction::SetTrue)
+                .required(false)
+                .help("Keep resending the packet whenever the device is quiet for a second and print every report it sends, including passive notifications"),
+        )
+        .arg(
+            Arg::new("product_id")
+                .long("product_id")
+                .required(false)
+                .help("Test the device with this product ID, e.g. 0x1718, instead of the Cloud II Wireless")
+                .value_parser(parse_hex_u16),
+        )
+        .arg(
+            Arg::new("interface")
+                .long("interface")
+                .required(false)
+                .help("Only use this interface number")
+                .value_parser(clap::value_parser!(i32)),
+        )
+        .get_matches();
 
-const PACKETS: [&[u8]; 12] = [
-    &BASE_PACKET,
-    &PACKET1,
-    &PACKET2,
-    &PACKET3,
-    &PACKET4,
-    &PACKET5,
-    &PACKET6,
-    &PACKET7,
-    &PACKET8,
-    &PACKET9,
-    &PACKET10,
-    &PACKET11,
-];
+    let product_ids = match matches.get_one::<u16>("product_id") {
+        Some(product_id) => vec![*product_id],
+        None => PRODUCT_IDS.to_vec(),
+    };
+    let interfaces = open_interfaces(&product_ids, matches.get_one::<i32>("interface"));
+    if interfaces.is_empty() {
+        eprintln!("No matching device found");
+        std::process::exit(1);
+    }
 
-fn main() {
+    match matches.get_one::<Vec<u8>>("packet") {
+        Some(packet) if matches.get_flag("loop") => loop {
+            send_to_all(&interfaces, packet);
+            for interface in &interfaces {
+                print_responses(interface, RESPONSE_TIMEOUT_MS);
+            }
+        },
+        Some(packet) => send_to_all(&interfaces, packet),
+        None if matches.get_flag("loop") => loop {
+            for interface in &interfaces {
+                print_responses(interface, RESPONSE_TIMEOUT_MS);
+            }
+        },
+        None => read_packets_from_stdin(&interfaces),
+    }
+}
+
+fn open_interfaces(product_ids: &[u16], interface_number: Option<&i32>) -> Vec<Interface> {
     let hidapi = HidApi::new().unwrap();
-    for device in hidapi.device_list() {
-        if VENDOR_IDS.contains(&device.vendor_id()) && PRODUCT_IDS.contains(&device.product_id()) {
-            test_device(device);
+    let mut interfaces = Vec::new();
+    for device_info in hidapi.device_list() {
+        if !VENDOR_IDS.contains(&device_info.vendor_id())
+            || !product_ids.contains(&device_info.product_id())
+            || interface_number.is_some_and(|number| *number != device_info.interface_number())
+        {
+            continue;
+        }
+        let name = format!(
+            "{:04X}:{:04X}:{}",
+            device_info.vendor_id(),
+            device_info.product_id(),
+            device_info.interface_number()
+        );
+        match device_info.open_device(&hidapi) {
+            Ok(device) => {
+                println!("Testing device: {name}");
+                interfaces.push(Interface { name, device });
+            }
+            Err(err) => eprintln!("Failed to open {name}: {err}"),
         }
     }
+    interfaces
 }
 
-fn test_device(device_info: &DeviceInfo) {
-    println!(
-        "Testing device: {}:{}:{}",
-        device_info.vendor_id(),
-        device_info.product_id(),
-        device_info.interface_number()
-    );
-    let hidapi = HidApi::new().unwrap();
-    let device = device_info.open_device(&hidapi).unwrap();
+fn read_packets_from_stdin(interfaces: &[Interface]) {
+    let stdin = std::io::stdin();
+    print!("> ");
+    let _ = std::io::stdout().flush();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if !line.trim().is_empty() {
+            match parse_packet(&line) {
+                Ok(packet) => send_to_all(interfaces, &packet),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+        print!("> ");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+fn send_to_all(interfaces: &[Interface], packet: &[u8]) {
+    for interface in interfaces {
+        println!("{} packet:   {}", interface.name, format_packet(packet));
+        if let Err(err) = interface.device.write(packet) {
+            println!("{} {err}", interface.name);
+            continue;
+        }
+        print_responses(interface, RESPONSE_TIMEOUT_MS);
+    }
+}
 
-    for packet in PACKETS {
-        let mut response_buffer = [0u8; 20];
-        let mut input_report_buffer = [0u8; 64];
-        input_report_buffer[0] = 6;
-        println!("  packet: {:?}", packet);
-        device.get_input_report(&mut input_report_buffer).unwrap();
-        let _ = device.write(packet).map_err(|err| println!("{err}"));
-        match device.read_timeout(&mut response_buffer, 1000) {
-            Err(err) => println!("{err}"),
-            Ok(len) => {
-                println!("  response: {:?}\n", &response_buffer[..len]);
+/// Prints every report that arrives within `timeout_ms`, the device may answer with more than one.
+fn print_responses(interface: &Interface, timeout_ms: i32) {
+    let mut timeout_ms = timeout_ms;
+    loop {
+        let mut response_buffer = [0u8; RESPONSE_BUFFER_SIZE];
+        match interface
+            .device
+            .read_timeout(&mut response_buffer, timeout_ms)
+        {
+            Ok(0) => return,
+            Ok(len) => println!(
+                "{} response: {}",
+                interface.name,
+                format_packet(&response_buffer[..len])
+            ),
+            Err(err) => {
+                println!("{} {err}", interface.name);
+                return;
             }
         }
+        timeout_ms = FOLLOW_UP_TIMEOUT_MS;
     }
 }
+
+fn format_packet(packet: &[u8]) -> String {
+    // trailing zeros are padding
+    let len = packet
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(1, |i| i + 1);
+    let bytes: Vec<String> = packet[..len]
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect();
+    format!("{} ({} bytes)", bytes.join(":"), packet.len())
+}
+
+/// Parses hex bytes separated by `:`, `,` or spaces, e.g. `06:00:02` or `06 00 02`.
+fn parse_packet(value: &str) -> Result<Vec<u8>, String> {
+    value
+        .split(|c: char| c == ':' || c == ',' || c.is_whitespace())
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| {
+            u8::from_str_radix(byte.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("Invalid hex byte: {byte}"))
+        })
+        .collect::<Result<Vec<u8>, String>>()
+        .and_then(|packet| {
+            if packet.is_empty() {
+                Err("Expected at least one byte".to_string())
+            } else {
+                Ok(packet)
+            }
+        })
+}
+
+fn parse_hex_u16(value: &str) -> Result<u16, String> {
+    u16::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("Invalid hex number: {value}"))
+}
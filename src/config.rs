@@ -0,0 +1,327 @@
+use std::{fs, path::PathBuf, sync::OnceLock, time::Duration};
+
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+use crate::devices::DeviceEvent;
+
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_LOW_BATTERY_THRESHOLD: u8 = 30;
+
+/// Battery levels below this are shown as low, set once at startup.
+pub static LOW_BATTERY_THRESHOLD: OnceLock<u8> = OnceLock::new();
+
+pub fn low_battery_threshold() -> u8 {
+    *LOW_BATTERY_THRESHOLD
+        .get()
+        .unwrap_or(&DEFAULT_LOW_BATTERY_THRESHOLD)
+}
+
+/// Directory for files of this application, e.g. `~/.config/hyper_headset` on Linux.
+pub fn config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME").map(|home| {
+        PathBuf::from(home)
+            .join("Library")
+            .join("Application Support")
+    });
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    let base: Option<PathBuf> = None;
+    base.map(|base| base.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Named set of settings from a `[profile.<name>]` table that are applied together.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub settings: Vec<DeviceEvent>,
+}
+
+/// Settings read from `config.toml` in the config directory.
+/// Every setting is optional, missing ones fall back to the command line defaults.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub refresh_interval_secs: Option<u64>,
+    pub active_refresh_every_n: Option<u64>,
+    pub low_battery_threshold: Option<u8>,
+    /// Hide small jumps of the battery level until they persist
+    pub battery_smoothing: Option<bool>,
+    /// Names of the notifications to show, e.g. `["low_battery", "charge_complete"]`
+    pub notify_on: Option<Vec<String>>,
+    /// File the battery state is written to for EWW widgets
+    pub eww_file: Option<String>,
+    /// The `[profile.<name>]` tables in the order they appear in the file
+    pub profiles: Vec<Profile>,
+}
+
+impl Config {
+    /// Returns the default config if the file does not exist.
+    pub fn load() -> Self {
+        let Some(path) = config_dir().map(|dir| dir.join(CONFIG_FILE)) else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Unknown tables and keys are ignored, invalid values are reported and skipped.
+    /// If the file is not valid TOML the error is printed and the defaults are used.
+    pub fn parse(content: &str) -> Self {
+        let mut table = match content.parse::<Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                eprintln!("Ignoring invalid {CONFIG_FILE}: {e}");
+                return Self::default();
+            }
+        };
+        Config {
+            refresh_interval_secs: take(&mut table, "refresh_interval_secs"),
+            active_refresh_every_n: take(&mut table, "active_refresh_every_n"),
+            low_battery_threshold: take(&mut table, "low_battery_threshold"),
+            battery_smoothing: take(&mut table, "battery_smoothing"),
+            notify_on: take(&mut table, "notify_on"),
+            eww_file: take(&mut table, "eww_file"),
+            profiles: take::<Table>(&mut table, "profile")
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(name, settings)| {
+                    let Value::Table(settings) = settings else {
+                        eprintln!("Ignoring invalid profile {name}, it is not a table");
+                        return None;
+                    };
+                    Some(Profile {
+                        name,
+                        settings: Settings::from_table(settings)
+                            .into_events()
+                            .into_iter()
+                            .map(|(_, event)| event)
+                            .collect(),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Removes `key` from `table` and converts its value.
+/// An invalid value is reported and skipped, so the other keys are still used.
+fn take<T: DeserializeOwned>(table: &mut Table, key: &str) -> Option<T> {
+    let value = table.remove(key)?;
+    match value.try_into() {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("Ignoring invalid setting {key}: {e}");
+            None
+        }
+    }
+}
+
+/// The keys of a `[profile.<name>]` table, in the order they are applied.
+#[derive(Debug, Default)]
+struct Settings {
+    mute: Option<bool>,
+    side_tone: Option<bool>,
+    /// negative volumes are sent as two's complement
+    side_tone_volume: Option<i16>,
+    surround_sound: Option<bool>,
+    voice_prompt: Option<bool>,
+    mute_playback: Option<bool>,
+    noise_gate: Option<bool>,
+    eq_slot: Option<u8>,
+    /// in minutes like the CLI
+    automatic_shutdown: Option<u64>,
+    /// dB of each band from 32Hz to 16kHz
+    equalizer: Option<Vec<f32>>,
+}
+
+impl Settings {
+    fn from_table(mut table: Table) -> Self {
+        Settings {
+            mute: take(&mut table, "mute"),
+            side_tone: take(&mut table, "side_tone"),
+            side_tone_volume: take(&mut table, "side_tone_volume"),
+            surround_sound: take(&mut table, "surround_sound"),
+            voice_prompt: take(&mut table, "voice_prompt"),
+            mute_playback: take(&mut table, "mute_playback"),
+            noise_gate: take(&mut table, "noise_gate"),
+            eq_slot: take(&mut table, "eq_slot"),
+            automatic_shutdown: take(&mut table, "automatic_shutdown"),
+            equalizer: take(&mut table, "equalizer"),
+        }
+    }
+
+    /// The key of each setting together with the event that applies it.
+    /// Values out of range are skipped with a message.
+    fn into_events(self) -> Vec<(&'static str, DeviceEvent)> {
+        let side_tone_volume = self.side_tone_volume.and_then(|volume| {
+            if (-128..=255).contains(&volume) {
+                Some(DeviceEvent::SideToneVolume(volume as u8))
+            } else {
+                eprintln!("Ignoring invalid setting: side_tone_volume = {volume}");
+                None
+            }
+        });
+        let automatic_shutdown =
+            self.automatic_shutdown
+                .and_then(|minutes| match minutes.checked_mul(60) {
+                    Some(secs) => Some(DeviceEvent::AutomaticShutdownAfter(Duration::from_secs(
+                        secs,
+                    ))),
+                    None => {
+                        eprintln!("Ignoring invalid setting: automatic_shutdown = {minutes}");
+                        None
+                    }
+                });
+        let equalizer = self.equalizer.and_then(|bands| {
+            if bands.iter().all(|db| (-12.0..=12.0).contains(db)) {
+                if let Ok(bands) = bands.clone().try_into() {
+                    return Some(DeviceEvent::Equalizer(bands));
+                }
+            }
+            eprintln!("Ignoring invalid setting: equalizer = {bands:?}");
+            None
+        });
+        [
+            ("mute", self.mute.map(DeviceEvent::Muted)),
+            ("side_tone", self.side_tone.map(DeviceEvent::SideToneOn)),
+            ("side_tone_volume", side_tone_volume),
+            (
+                "surround_sound",
+                self.surround_sound.map(DeviceEvent::SurroundSound),
+            ),
+            (
+                "voice_prompt",
+                self.voice_prompt.map(DeviceEvent::VoicePrompt),
+            ),
+            ("mute_playback", self.mute_playback.map(DeviceEvent::Silent)),
+            (
+                "noise_gate",
+                self.noise_gate.map(DeviceEvent::NoiseGateActive),
+            ),
+            ("eq_slot", self.eq_slot.map(DeviceEvent::ActiveEqSlot)),
+            ("automatic_shutdown", automatic_shutdown),
+            ("equalizer", equalizer),
+        ]
+        .into_iter()
+        .filter_map(|(key, event)| Some((key, event?)))
+        .collect()
+    }
+}
+
+/// Parses a settings file that uses the keys of a `[profile.<name>]` table, e.g. `side_tone = true`.
+/// Returns the key of each setting together with the event that applies it.
+/// Invalid settings are skipped with a message.
+pub fn parse_settings(content: &str) -> Vec<(String, DeviceEvent)> {
+    match content.parse::<Table>() {
+        Ok(table) => Settings::from_table(table)
+            .into_events()
+            .into_iter()
+            .map(|(key, event)| (key.to_string(), event))
+            .collect(),
+        Err(e) => {
+            eprintln!("Ignoring invalid settings: {e}");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings_and_profiles_in_file_order() {
+        let config = Config::parse(
+            r#"
+            refresh_interval_secs = 5
+            notify_on = ["low_battery", "charge_complete"]
+            eww_file = "/tmp/headset.json"
+            unknown_key = 1
+
+            [profile.Quiet]
+            mute = true
+            automatic_shutdown = 10 # minutes
+
+            [profile.Gaming]
+            side_tone_volume = -5
+            equalizer = [3, 2, 0, 0, -1, 0, 1, 2, 3, 3.5]
+            "#,
+        );
+        assert_eq!(config.refresh_interval_secs, Some(5));
+        assert_eq!(config.active_refresh_every_n, None);
+        assert_eq!(
+            config.notify_on,
+            Some(vec![
+                "low_battery".to_string(),
+                "charge_complete".to_string()
+            ])
+        );
+        assert_eq!(config.eww_file.as_deref(), Some("/tmp/headset.json"));
+
+        let names: Vec<&str> = config.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Quiet", "Gaming"]);
+        assert!(matches!(
+            config.profile("Quiet").unwrap().settings[..],
+            [
+                DeviceEvent::Muted(true),
+                DeviceEvent::AutomaticShutdownAfter(after)
+            ] if after == Duration::from_secs(600)
+        ));
+        assert!(matches!(
+            config.profile("Gaming").unwrap().settings[..],
+            [DeviceEvent::SideToneVolume(251), DeviceEvent::Equalizer(bands)] if bands[9] == 3.5
+        ));
+    }
+
+    #[test]
+    fn missing_or_invalid_files_use_the_defaults() {
+        let config = Config::parse("");
+        assert_eq!(config.refresh_interval_secs, None);
+        assert!(config.profiles.is_empty());
+
+        let config = Config::parse("refresh_interval_secs = ");
+        assert_eq!(config.refresh_interval_secs, None);
+    }
+
+    #[test]
+    fn invalid_values_only_skip_their_key() {
+        let config = Config::parse(
+            r#"
+            refresh_interval_secs = "soon"
+            low_battery_threshold = 20
+
+            [profile.Quiet]
+            mute = "yes"
+            side_tone = false
+            automatic_shutdown = 999999999999999999
+            "#,
+        );
+        assert_eq!(config.refresh_interval_secs, None);
+        assert_eq!(config.low_battery_threshold, Some(20));
+        assert!(matches!(
+            config.profile("Quiet").unwrap().settings[..],
+            [DeviceEvent::SideToneOn(false)]
+        ));
+    }
+
+    #[test]
+    fn parse_settings_skips_values_out_of_range() {
+        let settings =
+            parse_settings("side_tone = false\nside_tone_volume = 300\nequalizer = [13]");
+        assert_eq!(settings.len(), 1);
+        assert_eq!(settings[0].0, "side_tone");
+        assert!(matches!(settings[0].1, DeviceEvent::SideToneOn(false)));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use hyper_headset::{
+    config::{config_dir, Profile},
+    devices::{
+        json_string, Button, DeviceEvent, DeviceProperties, PropertyDescriptorWrapper, PropertyType,
+    },
+};
+
+const SOCKET_FILE: &str = "control.sock";
+/// Subscribers that do not read their button presses for this long are dropped,
+/// so they cannot stall the refresh loop
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub type SharedProperties = Arc<Mutex<Option<DeviceProperties>>>;
+/// Clients that sent `{"subscribe_buttons": true}`
+pub type ButtonSubscribers = Arc<Mutex<Vec<UnixStream>>>;
+
+fn socket_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(SOCKET_FILE))
+}
+
+/// Listens on a Unix socket for newline-delimited JSON commands such as
+/// `{"get_state": true}` or `{"set_mute": true}`.
+/// Every property that can be changed in the tray can be set with `set_<property name>`.
+/// `{"apply_eq_preset": "Gaming"}` applies the equalizer of the `[profile.Gaming]` table.
+/// `{"reconnect": true}` sets `reconnect` so the device is opened again.
+/// After `{"subscribe_buttons": true}` the client receives a line like
+/// `{"type": "button_press", "value": "volume_up"}` for every hardware button press.
+/// Each command is answered with a single line of JSON.
+pub fn spawn(
+    properties: SharedProperties,
+    update_sender: Sender<DeviceEvent>,
+    reconnect: Arc<AtomicBool>,
+    subscribers: ButtonSubscribers,
+    profiles: Vec<Profile>,
+) {
+    let Some(path) = socket_path() else {
+        eprintln!("Failed to start the control socket: no config directory");
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    // remove a stale socket of a previous run
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!(
+                "Failed to start the control socket at {}: {e}",
+                path.display()
+            );
+            return;
+        }
+    };
+    let profiles = Arc::new(profiles);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let client = Client {
+                properties: properties.clone(),
+                update_sender: update_sender.clone(),
+                reconnect: reconnect.clone(),
+                subscribers: subscribers.clone(),
+                profiles: profiles.clone(),
+            };
+            std::thread::spawn(move || handle_client(stream, client));
+        }
+    });
+}
+
+/// Everything a client connection needs to answer commands
+struct Client {
+    properties: SharedProperties,
+    update_sender: Sender<DeviceEvent>,
+    reconnect: Arc<AtomicBool>,
+    subscribers: ButtonSubscribers,
+    profiles: Arc<Vec<Profile>>,
+}
+
+fn handle_client(stream: UnixStream, client: Client) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match handle_command(&line, &client, &writer) {
+            Ok(response) => response,
+            Err(e) => format!("{{\"error\": {}}}", json_string(&e)),
+        };
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+fn handle_command(line: &str, client: &Client, stream: &UnixStream) -> Result<String, String> {
+    let (key, value) = line
+        .trim()
+        .strip_prefix('{')
+        .and_then(|line| line.strip_suffix('}'))
+        .and_then(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().trim_matches('"'), value.trim()))
+        .ok_or("Expected a JSON object with a single key")?;
+    if key == "reconnect" {
+        client.reconnect.store(true, Ordering::Relaxed);
+        return Ok("{\"ok\": true}".to_string());
+    }
+    if key == "subscribe_buttons" {
+        let subscriber = stream.try_clone().map_err(|e| e.to_string())?;
+        subscriber
+            .set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        client.subscribers.lock().unwrap().push(subscriber);
+        return Ok("{\"ok\": true}".to_string());
+    }
+    let properties = client.properties.lock().unwrap().clone();
+    let Some(properties) = properties else {
+        return Err("No compatible device found".to_string());
+    };
+
+    if key == "get_state" {
+        return Ok(format!("{{{}}}", properties.to_json_fields().join(", ")));
+    }
+
+    if key == "apply_eq_preset" {
+        if !properties.capabilities.can_set_equalizer {
+            return Err("The equalizer cannot be changed on this device".to_string());
+        }
+        let name = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .ok_or("Expected the name of a profile")?;
+        let profile = client
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| format!("Unknown profile: {name}"))?;
+        let equalizer = profile
+            .settings
+            .iter()
+            .find(|setting| matches!(setting, DeviceEvent::Equalizer(_)))
+            .ok_or_else(|| format!("The profile {name} has no equalizer"))?;
+        return send(client, *equalizer);
+    }
+
+    let name = match key
+        .strip_prefix("set_")
+        .ok_or_else(|| format!("Unknown command: {key}"))?
+    {
+        "mute" => "mic_muted",
+        name => name,
+    };
+    let event = properties
+        .get_properties()
+        .into_iter()
+        .find_map(|property| match property {
+            PropertyDescriptorWrapper::Int(property, _) if property.name == name => {
+                if property.property_type != PropertyType::ReadWrite {
+                    return Some(Err(format!("{name} cannot be changed")));
+                }
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Expected a number between 0 and 255 for {name}"));
+                Some(value.map(|value| (property.create_event)(value)))
+            }
+            PropertyDescriptorWrapper::Bool(property) if property.name == name => {
+                if property.property_type != PropertyType::ReadWrite {
+                    return Some(Err(format!("{name} cannot be changed")));
+                }
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Expected true or false for {name}"));
+                Some(value.map(|value| (property.create_event)(value)))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("Unknown property: {name}"))??
+        .ok_or_else(|| format!("{name} cannot be changed"))?;
+    send(client, event)
+}
+
+/// Hands the event to the refresh loop, which applies it to the device
+fn send(client: &Client, event: DeviceEvent) -> Result<String, String> {
+    client
+        .update_sender
+        .send(event)
+        .map_err(|_| "The device is not available".to_string())?;
+    Ok("{\"ok\": true}".to_string())
+}
+
+/// Sends the button press to every subscribed client and drops the disconnected ones.
+/// Clients whose socket buffer is full are dropped after `SUBSCRIBER_WRITE_TIMEOUT`.
+pub fn broadcast_button(subscribers: &ButtonSubscribers, button: Button) {
+    let line = DeviceEvent::ButtonPress(button).to_json();
+    subscribers
+        .lock()
+        .unwrap()
+        .retain_mut(|client| writeln!(client, "{line}").is_ok());
+}
@@ -0,0 +1,162 @@
+use std::{ffi::CString, sync::mpsc::Sender, time::Duration};
+
+use dbus::{
+    arg::{PropMap, Variant},
+    blocking::Connection,
+    channel::{MatchingReceiver, Sender as _},
+    message::MatchRule,
+    strings::ErrorName,
+    Message,
+};
+use hyper_headset::{config::Profile, devices::DeviceEvent};
+
+use crate::control_socket::SharedProperties;
+
+const BUS_NAME: &str = "org.hyperheadset.Control";
+const OBJECT_PATH: &str = "/org/hyperheadset/Control";
+const INTERFACE: &str = "org.hyperheadset.Control";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const ERROR_NAME: &str = "org.hyperheadset.Control.Error";
+/// How often the battery state is compared to announce changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The values exposed as D-Bus properties: `BatteryLevel` and `Charging`
+type BatteryState = (Option<u8>, Option<String>);
+
+/// Offers `GetBattery() -> y`, `SetMute(b)`, and `ApplyProfile(s)` as `org.hyperheadset.Control`
+/// on the session bus. `BatteryLevel` and `Charging` can be read with the standard properties
+/// interface, changes are announced with `PropertiesChanged`.
+pub fn spawn(
+    properties: SharedProperties,
+    update_sender: Sender<DeviceEvent>,
+    profiles: Vec<Profile>,
+) {
+    std::thread::spawn(move || {
+        let connection = match Connection::new_session() {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to connect to the session bus: {e}");
+                return;
+            }
+        };
+        if let Err(e) = connection.request_name(BUS_NAME, false, true, true) {
+            eprintln!("Failed to register {BUS_NAME}: {e}");
+            return;
+        }
+        let handler_properties = properties.clone();
+        connection.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |message, connection| {
+                let reply = handle_call(&message, &handler_properties, &update_sender, &profiles);
+                let _ = connection.send(reply);
+                true
+            }),
+        );
+        let mut announced = battery_state(&properties);
+        loop {
+            if let Err(e) = connection.process(POLL_INTERVAL) {
+                eprintln!("The D-Bus service stopped: {e}");
+                return;
+            }
+            let current = battery_state(&properties);
+            if current != announced {
+                let _ = connection.send(properties_changed(&current));
+                announced = current;
+            }
+        }
+    });
+}
+
+fn battery_state(properties: &SharedProperties) -> BatteryState {
+    let properties = properties.lock().unwrap();
+    let properties = properties.as_ref();
+    (
+        properties.and_then(|p| p.battery_level),
+        properties.and_then(|p| p.charging).map(|c| c.to_string()),
+    )
+}
+
+fn property_map((battery_level, charging): &BatteryState) -> PropMap {
+    let mut map = PropMap::new();
+    if let Some(battery_level) = battery_level {
+        map.insert(
+            "BatteryLevel".to_string(),
+            Variant(Box::new(*battery_level)),
+        );
+    }
+    if let Some(charging) = charging {
+        map.insert("Charging".to_string(), Variant(Box::new(charging.clone())));
+    }
+    map
+}
+
+fn properties_changed(state: &BatteryState) -> Message {
+    let changed = property_map(state);
+    let invalidated: Vec<&str> = ["BatteryLevel", "Charging"]
+        .into_iter()
+        .filter(|name| !changed.contains_key(*name))
+        .collect();
+    Message::new_signal(OBJECT_PATH, PROPERTIES_INTERFACE, "PropertiesChanged")
+        .expect("the signal names are valid")
+        .append3(INTERFACE, changed, invalidated)
+}
+
+fn handle_call(
+    message: &Message,
+    properties: &SharedProperties,
+    update_sender: &Sender<DeviceEvent>,
+    profiles: &[Profile],
+) -> Message {
+    let error = |text: &str| {
+        message.error(
+            &ErrorName::from(ERROR_NAME),
+            &CString::new(text).unwrap_or_default(),
+        )
+    };
+    let send = |events: &[DeviceEvent]| {
+        if properties.lock().unwrap().is_none() {
+            return error("No compatible device found");
+        }
+        for event in events {
+            if update_sender.send(*event).is_err() {
+                return error("The device is not available");
+            }
+        }
+        message.method_return()
+    };
+    if message.path().as_deref() != Some(OBJECT_PATH) {
+        return error("Unknown object");
+    }
+    let interface = message.interface();
+    let member = message.member();
+    match (interface.as_deref(), member.as_deref()) {
+        (Some(INTERFACE), Some("GetBattery")) => match battery_state(properties).0 {
+            Some(battery_level) => message.method_return().append1(battery_level),
+            None => error("The battery level is unknown"),
+        },
+        (Some(INTERFACE), Some("SetMute")) => match message.read1::<bool>() {
+            Ok(mute) => send(&[DeviceEvent::Muted(mute)]),
+            Err(_) => error("Expected a boolean"),
+        },
+        (Some(INTERFACE), Some("ApplyProfile")) => match message.read1::<&str>() {
+            Ok(name) => match profiles.iter().find(|profile| profile.name == name) {
+                Some(profile) => send(&profile.settings),
+                None => error(&format!("Unknown profile: {name}")),
+            },
+            Err(_) => error("Expected a profile name"),
+        },
+        (Some(PROPERTIES_INTERFACE), Some("GetAll")) => message
+            .method_return()
+            .append1(property_map(&battery_state(properties))),
+        (Some(PROPERTIES_INTERFACE), Some("Get")) => {
+            let Ok((_, name)) = message.read2::<&str, &str>() else {
+                return error("Expected an interface and a property name");
+            };
+            match property_map(&battery_state(properties)).remove(name) {
+                Some(value) => message.method_return().append1(value),
+                None => error(&format!("{name} is unknown")),
+            }
+        }
+        _ => error("Unknown method"),
+    }
+}
@@ -140,6 +140,8 @@ impl Device for CloudAlphaWireless {
         Some(tmp)
     }
 
+    // UNVERIFIED: neither the encoding nor the native range has been checked on a real headset,
+    // so `side_tone_volume_range` is not set and --side_tone_volume_percent is unavailable
     fn set_side_tone_volume_packet(&self, volume: u8) -> Option<Vec<u8>> {
         let mut tmp = BASE_PACKET.to_vec();
         tmp[2] = SET_SIDE_TONE_VOLUME_CMD_ID;
@@ -230,6 +232,10 @@ impl Device for CloudAlphaWireless {
         true
     }
 
+    fn model_name(&self) -> &'static str {
+        "CloudAlphaWireless"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
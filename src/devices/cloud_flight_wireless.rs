@@ -81,6 +81,10 @@ impl Device for CloudFlightWireless {
         }
     }
 
+    fn model_name(&self) -> &'static str {
+        "CloudFlightWireless"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
@@ -1,8 +1,8 @@
 use crate::{
     debug_println,
-    devices::{ChargingStatus, Device, DeviceEvent, DeviceState},
+    devices::{clamp_signed_side_tone_volume, ChargingStatus, Device, DeviceEvent, DeviceState},
 };
-use std::time::Duration;
+use std::{ops::RangeInclusive, time::Duration};
 
 const HP: u16 = 0x03F0;
 pub const VENDOR_IDS: [u16; 1] = [HP];
@@ -187,6 +187,10 @@ impl Device for CloudIICoreWireless {
         Some(tmp)
     }
 
+    fn side_tone_volume_range(&self) -> Option<RangeInclusive<i16>> {
+        Some(-5..=5)
+    }
+
     fn get_event_from_device_response(&self, response: &[u8]) -> Option<Vec<DeviceEvent>> {
         debug_println!("Read packet: {:?}", response);
         if response[0] != 102 {
@@ -223,14 +227,9 @@ impl Device for CloudIICoreWireless {
             }
             (GET_SIDE_TONE_VOLUME_CMD_ID, status, _, _)
             | (SET_SIDE_TONE_VOLUME_CMD_ID, status, _, _) => {
-                let status = if status >= 251 {
-                    (status as i32 | -256i32) as u8
-                } else if (0..=5).contains(&status) {
-                    status
-                } else {
-                    0u8
-                };
-                Some(vec![DeviceEvent::SideToneVolume(status)])
+                Some(vec![DeviceEvent::SideToneVolume(
+                    clamp_signed_side_tone_volume(status, 5),
+                )])
             }
             (GET_WIRELESS_STATUS_CMD_ID, status, _, _)
             | (WIRELESS_STATUS_RESPONSE_ID, status, _, _) => {
@@ -250,6 +249,10 @@ impl Device for CloudIICoreWireless {
         }
     }
 
+    fn model_name(&self) -> &'static str {
+        "CloudIICoreWireless"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
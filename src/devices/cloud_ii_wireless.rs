@@ -251,6 +251,10 @@ impl Device for CloudIIWireless {
         }
     }
 
+    fn model_name(&self) -> &'static str {
+        "CloudIIWireless"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
@@ -265,10 +269,9 @@ impl Device for CloudIIWireless {
         // so we ignore the error
         let mut input_report_buffer = [0u8; 64];
         input_report_buffer[0] = 6;
-        let _ = self
-            .state
-            .hid_device
-            .get_input_report(&mut input_report_buffer);
+        if let Some(hid_device) = &self.state.hid_device {
+            let _ = hid_device.get_input_report(&mut input_report_buffer);
+        }
     }
 
     fn allow_passive_refresh(&mut self) -> bool {
@@ -246,6 +246,10 @@ impl Device for CloudIIWirelessDTS {
         true
     }
 
+    fn model_name(&self) -> &'static str {
+        "CloudIIWirelessDTS"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
@@ -254,3 +258,35 @@ impl Device for CloudIIWirelessDTS {
         &mut self.state
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mute_responses() {
+        let device = CloudIIWirelessDTS::new_from_state(DeviceState::from_parts(None, HP, 0x0D93));
+        let mut response = BASE_PACKET;
+        response[3] = GET_MUTE_CMD_ID;
+        response[4] = 1;
+        let events = device.get_event_from_device_response(&response).unwrap();
+        assert!(matches!(events[..], [DeviceEvent::Muted(true)]));
+
+        response[3] = SET_MUTE_CMD_ID;
+        response[4] = 0;
+        let events = device.get_event_from_device_response(&response).unwrap();
+        assert!(matches!(events[..], [DeviceEvent::Muted(false)]));
+    }
+
+    #[test]
+    fn ignores_short_and_foreign_responses() {
+        let device = CloudIIWirelessDTS::new_from_state(DeviceState::from_parts(None, HP, 0x0D93));
+        assert!(device
+            .get_event_from_device_response(&BASE_PACKET[..6])
+            .is_none());
+        let mut response = BASE_PACKET;
+        response[0] = 33;
+        response[3] = GET_MUTE_CMD_ID;
+        assert!(device.get_event_from_device_response(&response).is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     debug_println,
-    devices::{ChargingStatus, Color, Device, DeviceEvent, DeviceState},
+    devices::{Button, ChargingStatus, Color, Device, DeviceEvent, DeviceState},
 };
 use std::time::Duration;
 
@@ -16,6 +16,8 @@ const MIC_HEADER: u8 = 0x05;
 const AUTO_SHUTDOWN_REPORT_ID: u8 = 0x0c;
 const AUTO_SHUTDOWN_CMD: [u8; 5] = [0x02, 0x03, 0x00, 0x00, 0x4a];
 const AUTO_SHUTDOWN_PACKET_SIZE: usize = 64;
+// The firmware ignores any other delay
+const AUTO_SHUTDOWN_OPTIONS: [u8; 4] = [0, 10, 20, 30];
 
 // Equalizer control (via SET_REPORT, report ID 0x0c)
 // Packet structure: 0c 02 03 00 00 5f [band] [value_hi] [value_lo] 00... (64 bytes total)
@@ -53,9 +55,9 @@ const SET_VOICE_PROMPT_COMMAND_ID: u8 = 0x0B;
 // Button report header (incoming from headset)
 const CONSUMER_CONTROL_HEADER: u8 = 0x0f;
 // Consumer control button values
-const _VOL_UP: u8 = 0x01;
-const _VOL_DOWN: u8 = 0x02;
-const _PLAY_PAUSE: u8 = 0x08;
+const VOL_UP: u8 = 0x01;
+const VOL_DOWN: u8 = 0x02;
+const PLAY_PAUSE: u8 = 0x08;
 
 fn make_auto_shutdown_packet(minutes: u64) -> Vec<u8> {
     let mut packet = vec![0u8; AUTO_SHUTDOWN_PACKET_SIZE];
@@ -153,6 +155,10 @@ impl Device for CloudIIISWireless {
         Some(make_auto_shutdown_packet(minutes))
     }
 
+    fn automatic_shutdown_options(&self) -> Option<&'static [u8]> {
+        Some(&AUTO_SHUTDOWN_OPTIONS)
+    }
+
     fn get_automatic_shut_down_packet(&self) -> Option<Vec<u8>> {
         let mut packet = BASE_PACKET.to_vec();
         packet[5] = GET_AUTO_POWER_OFF_COMMAND_ID;
@@ -272,12 +278,18 @@ impl Device for CloudIIISWireless {
                 Some(vec![DeviceEvent::Muted(muted)])
             }
             CONSUMER_CONTROL_HEADER => {
-                // Button press events - we log but don't need to store state
                 debug_println!(
                     "Consumer control event: 0x{:02x}",
                     response.get(1).unwrap_or(&0)
                 );
-                None
+                // releasing a button sends 0
+                let button = match response.get(1) {
+                    Some(&VOL_UP) => Button::VolumeUp,
+                    Some(&VOL_DOWN) => Button::VolumeDown,
+                    Some(&PLAY_PAUSE) => Button::PlayPause,
+                    _ => return None,
+                };
+                Some(vec![DeviceEvent::ButtonPress(button)])
             }
             RESPONSE_ID => parse_response(response),
             NOTIFICATION_ID => parse_notification(response),
@@ -292,6 +304,15 @@ impl Device for CloudIIISWireless {
         true
     }
 
+    // every answer repeats the command id of its query
+    fn supports_batched_refresh(&self) -> bool {
+        true
+    }
+
+    fn model_name(&self) -> &'static str {
+        "CloudIIISWireless"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
@@ -300,3 +321,94 @@ impl Device for CloudIIISWireless {
         &mut self.state
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_shutdown_packet_encodes_seconds_big_endian() {
+        let packet = make_auto_shutdown_packet(10);
+        assert_eq!(packet.len(), AUTO_SHUTDOWN_PACKET_SIZE);
+        assert_eq!(packet[0], AUTO_SHUTDOWN_REPORT_ID);
+        assert_eq!(packet[1..6], AUTO_SHUTDOWN_CMD);
+        assert_eq!(packet[6..8], [0x02, 0x58]);
+        assert_eq!(make_auto_shutdown_packet(0)[6..8], [0x00, 0x00]);
+        assert_eq!(make_auto_shutdown_packet(30)[6..8], [0x07, 0x08]);
+    }
+
+    #[test]
+    fn auto_shutdown_answer_is_read_as_seconds() {
+        let mut device =
+            CloudIIISWireless::new_from_state(DeviceState::from_parts(None, 0x03F0, 0x06BE));
+        let mut response = [0u8; 64];
+        response[0] = RESPONSE_ID;
+        response[5] = GET_AUTO_POWER_OFF_COMMAND_ID;
+        response[6..8].copy_from_slice(&[0x07, 0x08]);
+        let events = device.get_event_from_device_response(&response).unwrap();
+        assert!(matches!(
+            events[..],
+            [DeviceEvent::AutomaticShutdownAfter(after)] if after == Duration::from_secs(30 * 60)
+        ));
+        device.state.update_self_with_event(&events[0]);
+        assert_eq!(
+            device.state.device_properties.to_string_with_padding(0),
+            "Automatic shutdown after: 30min"
+        );
+    }
+
+    #[test]
+    fn auto_shutdown_rejects_delays_the_device_does_not_accept() {
+        let mut device =
+            CloudIIISWireless::new_from_state(DeviceState::from_parts(None, 0x03F0, 0x06BE));
+        let err = device
+            .try_apply(DeviceEvent::AutomaticShutdownAfter(Duration::from_secs(
+                15 * 60,
+            )))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "ERROR: Automatic shutdown must be one of 0, 10, 20, 30 minutes on this device"
+        );
+        // accepted delays get past the check and only fail because there is no device to write to
+        let err = device
+            .try_apply(DeviceEvent::AutomaticShutdownAfter(Duration::from_secs(
+                20 * 60,
+            )))
+            .unwrap_err();
+        assert!(err.starts_with("Failed to set automatic shutdown"), "{err}");
+    }
+
+    #[test]
+    fn equalizer_band_packet_has_header_and_band_index() {
+        for band_index in 0..10 {
+            let packet = make_equalizer_band_packet(band_index, 0.0);
+            assert_eq!(packet.len(), EQ_PACKET_SIZE);
+            assert_eq!(packet[0], 0x0c);
+            assert_eq!(packet[1..6], EQ_CMD);
+            assert_eq!(packet[6], band_index);
+        }
+    }
+
+    #[test]
+    fn equalizer_band_packet_encodes_centi_db_big_endian() {
+        let value = |db_value| {
+            let packet = make_equalizer_band_packet(0, db_value);
+            [packet[7], packet[8]]
+        };
+        assert_eq!(value(0.0), [0x00, 0x00]);
+        assert_eq!(value(12.0), [0x04, 0xB0]);
+        assert_eq!(value(-12.0), [0xFB, 0x50]);
+        assert_eq!(value(1.5), [0x00, 0x96]);
+    }
+
+    #[test]
+    fn equalizer_band_packet_clamps_to_12_db() {
+        let value = |db_value| {
+            let packet = make_equalizer_band_packet(0, db_value);
+            i16::from_be_bytes([packet[7], packet[8]])
+        };
+        assert_eq!(value(20.0), 1200);
+        assert_eq!(value(-20.0), -1200);
+    }
+}
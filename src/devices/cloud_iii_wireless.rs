@@ -1,8 +1,10 @@
 use crate::{
     debug_println,
-    devices::{ChargingStatus, Color, Device, DeviceEvent, DeviceState},
+    devices::{
+        clamp_signed_side_tone_volume, ChargingStatus, Color, Device, DeviceEvent, DeviceState,
+    },
 };
-use std::{time::Duration, vec};
+use std::{ops::RangeInclusive, time::Duration, vec};
 
 const HP: u16 = 0x03F0;
 pub const VENDOR_IDS: [u16; 1] = [HP];
@@ -174,6 +176,10 @@ impl Device for CloudIIIWireless {
         Some(tmp)
     }
 
+    fn side_tone_volume_range(&self) -> Option<RangeInclusive<i16>> {
+        Some(-5..=5)
+    }
+
     fn get_event_from_device_response(&self, response: &[u8]) -> Option<Vec<DeviceEvent>> {
         debug_println!("Read packet: {response:?}");
         if response[0] != 102 {
@@ -207,6 +213,10 @@ impl Device for CloudIIIWireless {
                 Some(vec![DeviceEvent::ProductColor(Color::from(color))])
             }
             (GET_SILENT_MODE_CMD_ID, silent, ..) => Some(vec![DeviceEvent::Silent(silent == 1)]),
+            (GET_SIDE_TONE_VOLUME_CMD_ID, volume, ..)
+            | (SET_SIDE_TONE_VOLUME_CMD_ID, volume, ..) => Some(vec![DeviceEvent::SideToneVolume(
+                clamp_signed_side_tone_volume(volume, 5),
+            )]),
             (GET_SIRK_CMD_ID, ..) => {
                 let mut flag = false;
                 for item in response.iter().take(18).skip(2) {
@@ -228,6 +238,16 @@ impl Device for CloudIIIWireless {
         true
     }
 
+    // every answer repeats the command id of its query, except the side tone state, which is
+    // not parsed yet, so side_tone_enabled always falls back to a serial query after the batch
+    fn supports_batched_refresh(&self) -> bool {
+        true
+    }
+
+    fn model_name(&self) -> &'static str {
+        "CloudIIIWireless"
+    }
+
     fn get_device_state(&self) -> &DeviceState {
         &self.state
     }
@@ -236,3 +256,27 @@ impl Device for CloudIIIWireless {
         &mut self.state
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_shutdown_answer_is_read_as_minutes() {
+        let mut device =
+            CloudIIIWireless::new_from_state(DeviceState::from_parts(None, 0x03F0, 0x05B7));
+        let mut response = BASE_PACKET;
+        response[1] = GET_AUTO_SHUTDOWN_CMD_ID;
+        response[2] = 30;
+        let events = device.get_event_from_device_response(&response).unwrap();
+        assert!(matches!(
+            events[..],
+            [DeviceEvent::AutomaticShutdownAfter(after)] if after == Duration::from_secs(30 * 60)
+        ));
+        device.state.update_self_with_event(&events[0]);
+        assert_eq!(
+            device.state.device_properties.to_string_with_padding(0),
+            "Automatic shutdown after: 30min"
+        );
+    }
+}
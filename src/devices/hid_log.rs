@@ -0,0 +1,128 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use crate::devices::{DeviceEvent, DeviceState, DEVICE_REGISTER};
+
+/// The file all HID traffic is appended to and when the recording started
+static RECORDING: OnceLock<Mutex<(File, Instant)>> = OnceLock::new();
+
+/// Records every HID report written to or read from the device to `path`.
+///
+/// The `.hidlog` format has one report per line, e.g. `1.042 read 06:FF:BB:02:00:5A`.
+/// The line `# device 03F0:018B` names the vendor and product ID of the recorded device,
+/// it is written when the device is connected.
+pub fn start_recording(path: &Path) -> io::Result<()> {
+    let file = File::create(path)?;
+    RECORDING
+        .set(Mutex::new((file, Instant::now())))
+        .map_err(|_| io::Error::other("A recording is already running"))
+}
+
+pub(crate) fn record_device(vendor_id: u16, product_id: u16) {
+    write_line(|_| format!("# device {vendor_id:04X}:{product_id:04X}"));
+}
+
+/// `direction` is either "write" or "read"
+pub(crate) fn record(direction: &str, report: &[u8]) {
+    write_line(|started| {
+        let elapsed = started.elapsed();
+        format!(
+            "{}.{:03} {direction} {}",
+            elapsed.as_secs(),
+            elapsed.subsec_millis(),
+            format_report(report)
+        )
+    });
+}
+
+/// Does nothing unless a recording was started
+fn write_line(line: impl FnOnce(Instant) -> String) {
+    let Some(recording) = RECORDING.get() else {
+        return;
+    };
+    let (file, started) = &mut *recording.lock().unwrap();
+    if let Err(e) = writeln!(file, "{}", line(*started)) {
+        eprintln!("Failed to record HID traffic: {e}");
+    }
+}
+
+fn format_report(report: &[u8]) -> String {
+    report
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn parse_report(report: &str) -> Option<Vec<u8>> {
+    report
+        .split(':')
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect()
+}
+
+/// A report read from the device during a recording and the events the driver decodes from it
+pub struct ReplayedReport {
+    /// Seconds since the recording started, as written in the log
+    pub time: String,
+    pub report: Vec<u8>,
+    /// None if the driver does not understand the report
+    pub events: Option<Vec<DeviceEvent>>,
+}
+
+/// Feeds every report read during a recording into the parser of the driver for the
+/// recorded device. No hardware is needed, written reports are skipped.
+pub fn replay(path: &Path) -> Result<Vec<ReplayedReport>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let (vendor_id, product_id) = content
+        .lines()
+        .find_map(|line| line.strip_prefix("# device "))
+        .and_then(|ids| ids.split_once(':'))
+        .and_then(|(vendor_id, product_id)| {
+            Some((
+                u16::from_str_radix(vendor_id, 16).ok()?,
+                u16::from_str_radix(product_id, 16).ok()?,
+            ))
+        })
+        .ok_or("The recording does not name a device")?;
+    let entry = DEVICE_REGISTER
+        .iter()
+        .find(|e| e.vendor_ids.contains(&vendor_id) && e.product_ids.contains(&product_id))
+        .ok_or_else(|| {
+            format!("No driver for the recorded device {vendor_id:04X}:{product_id:04X}")
+        })?;
+    let device = (entry.factory)(DeviceState::from_parts(None, vendor_id, product_id));
+
+    let mut reports = Vec::new();
+    for line in content.lines().filter(|line| !line.starts_with('#')) {
+        let mut parts = line.split_whitespace();
+        let (Some(time), Some(direction), Some(report)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if direction != "read" {
+            continue;
+        }
+        let report = parse_report(report).ok_or_else(|| format!("Invalid report: {line}"))?;
+        if device.is_keepalive(&report) {
+            continue;
+        }
+        // drivers expect the report at the start of a full response buffer
+        let mut buffer = device.get_response_buffer();
+        let len = report.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&report[..len]);
+        reports.push(ReplayedReport {
+            time: time.to_string(),
+            events: device.get_event_from_device_response(&buffer),
+            report,
+        });
+    }
+    Ok(reports)
+}
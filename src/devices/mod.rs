@@ -5,6 +5,7 @@ pub mod cloud_ii_wireless;
 pub mod cloud_ii_wireless_dts;
 pub mod cloud_iii_s_wireless;
 pub mod cloud_iii_wireless;
+pub mod hid_log;
 
 use crate::{
     debug_println,
@@ -14,16 +15,79 @@ use crate::{
         cloud_ii_wireless_dts::CloudIIWirelessDTS, cloud_iii_s_wireless::CloudIIISWireless,
         cloud_iii_wireless::CloudIIIWireless,
     },
+    info_println,
 };
-use hidapi::{HidApi, HidDevice, HidError};
+use hidapi::{DeviceInfo, HidApi, HidDevice, HidError};
 use std::{
     collections::HashSet,
     fmt::{Debug, Display},
-    time::Duration,
+    ops::RangeInclusive,
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
 };
 use thistermination::TerminationFull;
 
+/// The name of every query and the events decoded from its answer, None if there was none
+pub type SelfTestReport = Vec<(&'static str, Option<Vec<DeviceEvent>>)>;
+
 const PASSIVE_REFRESH_TIME_OUT: Duration = Duration::from_secs(2);
+/// Upper bound for the delay between retries of an unanswered query
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+/// Battery samples older than this are not used to compute the battery rate
+const BATTERY_RATE_WINDOW: Duration = Duration::from_secs(30 * 60);
+/// The samples have to span at least this long before a battery rate is reported
+const BATTERY_RATE_MIN_SPAN: Duration = Duration::from_secs(10 * 60);
+/// With battery smoothing, smaller changes are only shown after `BATTERY_SMOOTHING_READINGS` equal readings
+const BATTERY_SMOOTHING_THRESHOLD: u8 = 5;
+const BATTERY_SMOOTHING_READINGS: usize = 3;
+/// Link quality suffixes for 0-3 bars, a coarse view is enough to spot dropouts in the tray
+const LINK_QUALITY_SUFFIXES: [&str; 4] = ["% ▁▁▁", "% ▂▁▁", "% ▂▄▁", "% ▂▄▆"];
+
+/// Side tone volumes of some devices are signed and sent as two's complement, e.g. 251 is -5.
+/// Returns the raw value if it lies within -max..=max and 0 otherwise.
+pub(crate) fn clamp_signed_side_tone_volume(raw: u8, max: i8) -> u8 {
+    if (raw as i8).unsigned_abs() <= max.unsigned_abs() {
+        raw
+    } else {
+        0
+    }
+}
+
+/// Interprets a raw side tone volume in the given device-native range.
+/// Ranges with a negative start are signed.
+pub fn native_side_tone_volume(raw: u8, range: &RangeInclusive<i16>) -> i16 {
+    if *range.start() < 0 {
+        raw as i8 as i16
+    } else {
+        raw as i16
+    }
+}
+
+/// Maps 0-100% to the device-native range, the inverse of `DeviceProperties::side_tone_volume_percent`.
+/// Negative volumes are returned as two's complement.
+pub fn side_tone_volume_from_percent(percent: u8, range: &RangeInclusive<i16>) -> u8 {
+    let span = (range.end() - range.start()) as i32;
+    let native = *range.start() as i32 + (percent.min(100) as i32 * span + 50) / 100;
+    native as u8
+}
+
+/// Quotes and escapes a string for JSON output, e.g. product names may contain `"`
+pub fn json_string(value: &str) -> String {
+    let mut json = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
 
 pub fn format_int_value(value: u8, suffix: &str) -> String {
     if value == 0 && suffix == "min" {
@@ -81,6 +145,8 @@ const DEVICE_REGISTER: &[DeviceEntry] = &[
 
 const RESPONSE_BUFFER_SIZE: usize = 256;
 pub const RESPONSE_DELAY: Duration = Duration::from_millis(50);
+/// Number of equalizer bands, see `Device::set_equalizer_band_packet`
+pub const NUM_BANDS: usize = 10;
 
 /// A connected headset, either over USB HID (the dongle) or, as a fallback on
 /// Linux, over Bluetooth. Frontends (tray, CLI) consume this uniformly via the
@@ -92,6 +158,15 @@ pub enum Headset {
 }
 
 impl Headset {
+    /// Name of the implementation handling the headset
+    pub fn model_name(&self) -> &'static str {
+        match self {
+            Headset::Hid(device) => device.model_name(),
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => "Bluetooth",
+        }
+    }
+
     pub fn device_properties(&self) -> DeviceProperties {
         match self {
             Headset::Hid(device) => device.get_device_state().device_properties.clone(),
@@ -116,6 +191,39 @@ impl Headset {
         }
     }
 
+    pub fn settle_delay(&self) -> Duration {
+        match self {
+            Headset::Hid(device) => device.settle_delay(),
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => Duration::ZERO,
+        }
+    }
+
+    pub fn quick_battery(&mut self) -> Result<Option<u8>, DeviceError> {
+        match self {
+            Headset::Hid(device) => device.quick_battery(),
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(bt) => {
+                bt.refresh()?;
+                Ok(bt.device_properties().battery_level)
+            }
+        }
+    }
+
+    /// Actively refreshes the state and retries once if some queries went unanswered,
+    /// e.g. because the headset was still waking up.
+    pub fn active_refresh_state_with_retry(&mut self) -> Result<(), DeviceError> {
+        self.active_refresh_state()?;
+        if let Headset::Hid(device) = self {
+            if !device.get_device_state().unanswered_queries.is_empty() {
+                debug_println!("Partial refresh, retrying");
+                std::thread::sleep(RESPONSE_DELAY);
+                device.active_refresh_state()?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn allow_passive_refresh(&mut self) -> bool {
         match self {
             Headset::Hid(device) => device.allow_passive_refresh(),
@@ -124,6 +232,69 @@ impl Headset {
         }
     }
 
+    /// Number of consecutive readings a new charging status must persist before it is accepted.
+    /// 1 accepts every change immediately.
+    pub fn set_charging_debounce(&mut self, readings: usize) {
+        match self {
+            Headset::Hid(device) => device.get_device_state_mut().charging_debounce = readings,
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => (),
+        }
+    }
+
+    /// Hide small jumps of the battery level until they persist over several readings
+    pub fn set_battery_smoothing(&mut self, enable: bool) {
+        match self {
+            Headset::Hid(device) => device.get_device_state_mut().battery_smoothing = enable,
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => (),
+        }
+    }
+
+    /// How often an unanswered query is repeated during an active refresh
+    pub fn set_query_retries(&mut self, retries: usize) {
+        match self {
+            Headset::Hid(device) => device.get_device_state_mut().query_retries = retries,
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => (),
+        }
+    }
+
+    /// Whether all queries are sent at once during an active refresh, see `Device::batched_refresh`
+    pub fn set_batched_refresh(&mut self, batched: bool) {
+        match self {
+            Headset::Hid(device) => device.get_device_state_mut().batched_refresh = batched,
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => (),
+        }
+    }
+
+    /// Forwards the buttons pressed on the headset to `sender`
+    pub fn set_button_sender(&mut self, sender: Sender<Button>) {
+        match self {
+            Headset::Hid(device) => device.get_device_state_mut().button_sender = Some(sender),
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => (),
+        }
+    }
+
+    /// Sends every query once and returns the events decoded from each answer.
+    pub fn self_test(&mut self) -> Result<SelfTestReport, DeviceError> {
+        match self {
+            Headset::Hid(device) => device.self_test(),
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => Err(DeviceError::NotSupported()),
+        }
+    }
+
+    pub fn apply_eq_preset(&mut self, bands: &[f32; NUM_BANDS]) -> Result<(), DeviceError> {
+        match self {
+            Headset::Hid(device) => device.apply_eq_preset(bands),
+            #[cfg(target_os = "linux")]
+            Headset::Bluetooth(_) => Err(DeviceError::NotSupported()),
+        }
+    }
+
     pub fn try_apply(&mut self, command: DeviceEvent) -> Result<(), String> {
         match self {
             Headset::Hid(device) => device.try_apply(command),
@@ -135,10 +306,172 @@ impl Headset {
     }
 }
 
+/// A compatible HID device as reported by the OS, without opening it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompatibleDevice {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product_string: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+impl Display for CompatibleDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "vendorID: 0x{:04X} productID: 0x{:04X} name: {} serial: {}",
+            self.vendor_id,
+            self.product_id,
+            self.product_string.as_deref().unwrap_or("Unknown"),
+            self.serial_number.as_deref().unwrap_or("Unknown")
+        )
+    }
+}
+
+/// Maps errors caused by missing permissions, e.g. missing udev rules, to `PermissionDenied`
+/// and all others with `otherwise`.
+fn permission_error_or(error: HidError, otherwise: fn(HidError) -> DeviceError) -> DeviceError {
+    let message = error.to_string();
+    let lowercase = message.to_lowercase();
+    if lowercase.contains("permission denied") || lowercase.contains("access is denied") {
+        DeviceError::PermissionDenied(message)
+    } else {
+        otherwise(error)
+    }
+}
+
+fn hid_api() -> Result<HidApi, DeviceError> {
+    HidApi::new().map_err(|e| permission_error_or(e, DeviceError::HidError))
+}
+
+fn registered_ids() -> (Vec<u16>, Vec<u16>) {
+    let all_product_ids: Vec<u16> = DEVICE_REGISTER
+        .iter()
+        .flat_map(|e| e.product_ids.iter().copied())
+        .collect();
+    let all_vendor_ids: Vec<u16> = DEVICE_REGISTER
+        .iter()
+        .flat_map(|e| e.vendor_ids.iter().copied())
+        .collect();
+    (all_product_ids, all_vendor_ids)
+}
+
+/// Lists all attached HID devices that are supported.
+/// Devices exposing multiple interfaces are only listed once.
+pub fn list_compatible_devices() -> Result<Vec<CompatibleDevice>, DeviceError> {
+    let (all_product_ids, all_vendor_ids) = registered_ids();
+    let hid_api = hid_api()?;
+    let mut devices: Vec<CompatibleDevice> = Vec::new();
+    for info in hid_api.device_list() {
+        if !all_product_ids.contains(&info.product_id())
+            || !all_vendor_ids.contains(&info.vendor_id())
+        {
+            continue;
+        }
+        let device = CompatibleDevice {
+            vendor_id: info.vendor_id(),
+            product_id: info.product_id(),
+            product_string: info.product_string().map(|s| s.to_string()),
+            serial_number: info.serial_number().map(|s| s.to_string()),
+        };
+        if !devices.contains(&device) {
+            devices.push(device);
+        }
+    }
+    Ok(devices)
+}
+
+/// A single HID interface of a HyperX or HP device, used to add support for new headsets.
+#[derive(Debug, Clone)]
+pub struct HidInterface {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product_string: Option<String>,
+    pub manufacturer_string: Option<String>,
+    pub serial_number: Option<String>,
+    pub interface_number: i32,
+    pub usage_page: u16,
+    pub usage: u16,
+    /// None if the interface could not be opened
+    pub report_descriptor_length: Option<usize>,
+    pub supported: bool,
+}
+
+impl Display for HidInterface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = || "Unknown".to_string();
+        writeln!(
+            f,
+            "vendorID: 0x{:04X} productID: 0x{:04X}{}",
+            self.vendor_id,
+            self.product_id,
+            if self.supported { " (supported)" } else { "" }
+        )?;
+        writeln!(
+            f,
+            "  product: {}",
+            self.product_string.clone().unwrap_or_else(unknown)
+        )?;
+        writeln!(
+            f,
+            "  manufacturer: {}",
+            self.manufacturer_string.clone().unwrap_or_else(unknown)
+        )?;
+        writeln!(
+            f,
+            "  serial: {}",
+            self.serial_number.clone().unwrap_or_else(unknown)
+        )?;
+        writeln!(f, "  interface: {}", self.interface_number)?;
+        writeln!(
+            f,
+            "  usage page: 0x{:04X} usage: 0x{:04X}",
+            self.usage_page, self.usage
+        )?;
+        write!(
+            f,
+            "  report descriptor length: {}",
+            self.report_descriptor_length
+                .map_or_else(unknown, |length| length.to_string())
+        )
+    }
+}
+
+/// Lists every HID interface of a known vendor, including unsupported products.
+pub fn list_hid_interfaces() -> Result<Vec<HidInterface>, DeviceError> {
+    let (all_product_ids, all_vendor_ids) = registered_ids();
+    let hid_api = hid_api()?;
+    let mut interfaces = Vec::new();
+    for info in hid_api.device_list() {
+        if !all_vendor_ids.contains(&info.vendor_id()) {
+            continue;
+        }
+        // the largest descriptor hidapi can return
+        let mut descriptor = [0u8; 4096];
+        let report_descriptor_length = info
+            .open_device(&hid_api)
+            .and_then(|device| device.get_report_descriptor(&mut descriptor))
+            .ok();
+        interfaces.push(HidInterface {
+            vendor_id: info.vendor_id(),
+            product_id: info.product_id(),
+            product_string: info.product_string().map(|s| s.to_string()),
+            manufacturer_string: info.manufacturer_string().map(|s| s.to_string()),
+            serial_number: info.serial_number().map(|s| s.to_string()),
+            interface_number: info.interface_number(),
+            usage_page: info.usage_page(),
+            usage: info.usage(),
+            report_descriptor_length,
+            supported: all_product_ids.contains(&info.product_id()),
+        });
+    }
+    Ok(interfaces)
+}
+
 /// Connect to a compatible headset: a USB HID dongle if present, otherwise
 /// (on Linux) fall back to a Bluetooth-connected HyperX headset.
 pub fn connect_compatible_device() -> Result<Headset, DeviceError> {
-    match connect_hid_device() {
+    match connect_hid_device(None) {
         Ok(device) => Ok(Headset::Hid(device)),
         Err(error) => {
             #[cfg(target_os = "linux")]
@@ -152,67 +485,72 @@ pub fn connect_compatible_device() -> Result<Headset, DeviceError> {
     }
 }
 
-fn connect_hid_device() -> Result<Box<dyn Device>, DeviceError> {
-    let all_product_ids: Vec<u16> = DEVICE_REGISTER
-        .iter()
-        .flat_map(|e| e.product_ids.iter().copied())
-        .collect();
-    let all_vendor_ids: Vec<u16> = DEVICE_REGISTER
-        .iter()
-        .flat_map(|e| e.vendor_ids.iter().copied())
-        .collect();
-    let states = DeviceState::new(&all_product_ids, &all_vendor_ids)?;
+/// Connect to the compatible USB HID device with the given serial number.
+/// Useful if multiple compatible devices are attached.
+pub fn connect_device_by_serial(serial_number: &str) -> Result<Headset, DeviceError> {
+    connect_hid_device(Some(serial_number)).map(Headset::Hid)
+}
+
+fn connect_hid_device(serial_number: Option<&str>) -> Result<Box<dyn Device>, DeviceError> {
+    let (all_product_ids, all_vendor_ids) = registered_ids();
+    let states = DeviceState::new(&all_product_ids, &all_vendor_ids, serial_number)?;
     debug_println!("Found device selecting handler");
 
+    let mut devices = Vec::new();
+    for state in states {
+        let entry = DEVICE_REGISTER
+            .iter()
+            .find(|e| {
+                e.vendor_ids.contains(&state.device_properties.vendor_id)
+                    && e.product_ids.contains(&state.device_properties.product_id)
+            })
+            .ok_or(DeviceError::NoDeviceFound())?;
+        devices.push((entry.factory)(state));
+    }
+    // Prefer the interface the handler expects, the sort is stable so the order is otherwise kept
+    devices.sort_by_key(|device| {
+        device
+            .expected_interface_number()
+            .is_some_and(|n| n != device.get_device_state().interface_number)
+    });
+    prefer_powered_on(&mut devices);
+
     // On Linux and MacOS we can just take the first
     #[cfg(not(target_os = "windows"))]
     {
-        let state = states
+        let mut device = devices
             .into_iter()
             .next()
             .ok_or(DeviceError::NoDeviceFound())?;
-        eprintln!(
-            "Connecting to {}",
-            state
+        info_println!(
+            "Connecting to {} (driver: {})",
+            device
+                .get_device_state()
                 .device_properties
                 .device_name
                 .clone()
-                .unwrap_or("???".to_string())
+                .unwrap_or("???".to_string()),
+            device.model_name()
         );
-        let entry = DEVICE_REGISTER
-            .iter()
-            .find(|e| {
-                e.vendor_ids.contains(&state.device_properties.vendor_id)
-                    && e.product_ids.contains(&state.device_properties.product_id)
-            })
-            .ok_or(DeviceError::NoDeviceFound())?;
-
-        let mut device = (entry.factory)(state);
         device.init_capabilities();
+        record_device(device.as_ref());
         Ok(device)
     }
     // On Windows we have to check which interface can be used
     #[cfg(target_os = "windows")]
     {
         let mut device = None;
-        for state in states {
-            eprintln!(
-                "Try to connect to {}",
-                state
+        for mut test_device in devices {
+            info_println!(
+                "Try to connect to {} (driver: {})",
+                test_device
+                    .get_device_state()
                     .device_properties
                     .device_name
                     .clone()
-                    .unwrap_or("???".to_string())
+                    .unwrap_or("???".to_string()),
+                test_device.model_name()
             );
-            let entry = DEVICE_REGISTER
-                .iter()
-                .find(|e| {
-                    e.vendor_ids.contains(&state.device_properties.vendor_id)
-                        && e.product_ids.contains(&state.device_properties.product_id)
-                })
-                .ok_or(DeviceError::NoDeviceFound())?;
-
-            let mut test_device = (entry.factory)(state);
             test_device.init_capabilities();
 
             let probe_packet = test_device
@@ -221,14 +559,11 @@ fn connect_hid_device() -> Result<Box<dyn Device>, DeviceError> {
                 .next()
                 .expect("Why is there a device without packets ???");
 
-            test_device.prepare_write();
-            if let Err(_e) = test_device
-                .get_device_state()
-                .write_hid_report(&probe_packet)
-            {
+            if let Err(_e) = test_device.write_packet(&probe_packet) {
                 debug_println!("Failed to open: {_e:?}");
                 continue;
             } else {
+                record_device(test_device.as_ref());
                 device = Some(test_device);
                 break;
             }
@@ -237,19 +572,178 @@ fn connect_hid_device() -> Result<Box<dyn Device>, DeviceError> {
     }
 }
 
+/// Names the device in the HID recording, if one is running.
+fn record_device(device: &dyn Device) {
+    let properties = &device.get_device_state().device_properties;
+    hid_log::record_device(properties.vendor_id, properties.product_id);
+}
+
+/// If several dongles are attached, moves the ones whose headset answers a status query to the front.
+/// Only the first interface of each dongle is queried, the order is otherwise kept.
+fn prefer_powered_on(devices: &mut [Box<dyn Device>]) {
+    let dongle_id = |device: &dyn Device| {
+        device
+            .get_device_state()
+            .dongle_id()
+            .map(|id| id.to_string())
+    };
+    let mut dongle_ids = Vec::new();
+    for device in devices.iter() {
+        let id = dongle_id(device.as_ref());
+        if !dongle_ids.contains(&id) {
+            dongle_ids.push(id);
+        }
+    }
+    if dongle_ids.len() < 2 {
+        return;
+    }
+    let mut powered_on = Vec::new();
+    for id in dongle_ids {
+        if let Some(device) = devices.iter_mut().find(|d| dongle_id(d.as_ref()) == id) {
+            if device.is_powered_on() {
+                powered_on.push(id);
+            }
+        }
+    }
+    devices.sort_by_key(|device| !powered_on.contains(&dongle_id(device.as_ref())));
+}
+
 #[derive(Debug)]
 pub struct DeviceState {
-    pub hid_device: HidDevice,
+    /// None while replaying a recording, see `hid_log::replay`
+    pub hid_device: Option<HidDevice>,
+    pub interface_number: i32,
+    /// Platform specific path of the opened interface, None while replaying a recording
+    hid_path: Option<String>,
+    /// Properties whose queries went unanswered during the last active refresh
+    pub unanswered_queries: Vec<&'static str>,
+    /// How often an unanswered query is repeated during an active refresh, 0 by default
+    pub query_retries: usize,
+    /// Send all queries at once if the device supports it, off by default
+    pub batched_refresh: bool,
+    /// Number of consecutive readings a new charging status must persist before it is accepted
+    pub charging_debounce: usize,
+    /// A charging status that differs from the accepted one and how often it was read in a row
+    pending_charging: Option<(ChargingStatus, usize)>,
+    /// Recent battery levels used to compute the battery rate
+    battery_samples: Vec<(Instant, u8)>,
+    /// Hide small jumps of the battery level until they persist
+    pub battery_smoothing: bool,
+    /// The last `BATTERY_SMOOTHING_READINGS` battery readings
+    recent_battery_levels: Vec<u8>,
+    /// Receives the buttons pressed on the headset
+    pub button_sender: Option<Sender<Button>>,
     pub device_properties: DeviceProperties,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Which properties of a device can be changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub can_set_mute: bool,
+    pub can_set_surround_sound: bool,
+    pub can_set_side_tone: bool,
+    pub can_set_automatic_shutdown: bool,
+    pub can_set_side_tone_volume: bool,
+    pub can_set_voice_prompt: bool,
+    pub can_set_silent_mode: bool,
+    pub can_set_equalizer: bool,
+    pub can_set_noise_gate: bool,
+    pub can_set_led: bool,
+    pub can_set_active_eq_slot: bool,
+}
+
+impl Capabilities {
+    /// Whether the setting `event` applies can be changed, false for events that are only reported.
+    pub fn supports(&self, event: &DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::Muted(_) => self.can_set_mute,
+            DeviceEvent::AutomaticShutdownAfter(_) => self.can_set_automatic_shutdown,
+            DeviceEvent::SideToneOn(_) => self.can_set_side_tone,
+            DeviceEvent::SideToneVolume(_) => self.can_set_side_tone_volume,
+            DeviceEvent::VoicePrompt(_) => self.can_set_voice_prompt,
+            DeviceEvent::SurroundSound(_) => self.can_set_surround_sound,
+            DeviceEvent::Silent(_) => self.can_set_silent_mode,
+            DeviceEvent::NoiseGateActive(_) => self.can_set_noise_gate,
+            DeviceEvent::LedState(_, _) | DeviceEvent::LedBrightness(_) => self.can_set_led,
+            DeviceEvent::ActiveEqSlot(_) => self.can_set_active_eq_slot,
+            DeviceEvent::Equalizer(_) => self.can_set_equalizer,
+            _ => false,
+        }
+    }
+
+    /// Whether the property with the given name, as used in `Device::get_named_query_packets`,
+    /// can be changed. The equalizer is called "equalizer".
+    pub fn can_set_property(&self, name: &str) -> bool {
+        match name {
+            "mic_muted" => self.can_set_mute,
+            "automatic_shutdown_interval" => self.can_set_automatic_shutdown,
+            "side_tone_enabled" => self.can_set_side_tone,
+            "side_tone_volume" => self.can_set_side_tone_volume,
+            "voice_prompt_enabled" => self.can_set_voice_prompt,
+            "surround_sound_enabled" => self.can_set_surround_sound,
+            "playback_muted" => self.can_set_silent_mode,
+            "noise_gate_enabled" => self.can_set_noise_gate,
+            "led" => self.can_set_led,
+            "active_eq_slot" => self.can_set_active_eq_slot,
+            "equalizer" => self.can_set_equalizer,
+            _ => false,
+        }
+    }
+}
+
+/// How a single property of a device can be accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureAccess {
+    NotSupported,
+    /// Can be changed but the device does not report it, e.g. the equalizer
+    WriteOnly,
+    /// Reported by the device but only changeable on the headset, e.g. mute on the Cloud II
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Everything a device supports, see `Device::supported_features`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureSet {
+    /// Properties that can be changed
+    pub capabilities: Capabilities,
+    /// Names of the properties that can be queried, as used in `Device::get_named_query_packets`
+    pub readable: Vec<&'static str>,
+    pub side_tone_volume_range: Option<RangeInclusive<i16>>,
+    pub automatic_shutdown_options: Option<&'static [u8]>,
+}
+
+impl FeatureSet {
+    pub fn can_read(&self, name: &str) -> bool {
+        self.readable.contains(&name)
+    }
+
+    /// Whether the property with the given name can be read, changed, or both
+    pub fn access(&self, name: &str) -> FeatureAccess {
+        match (
+            self.can_read(name),
+            self.capabilities.can_set_property(name),
+        ) {
+            (true, true) => FeatureAccess::ReadWrite,
+            (true, false) => FeatureAccess::ReadOnly,
+            (false, true) => FeatureAccess::WriteOnly,
+            (false, false) => FeatureAccess::NotSupported,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct DeviceProperties {
     pub product_id: u16,
     pub vendor_id: u16,
     pub device_name: Option<String>,
+    pub serial_number: Option<String>,
+    /// bcdDevice of the dongle's USB descriptor, not the firmware version reported over HID
+    pub dongle_usb_release: Option<String>,
     pub battery_level: Option<u8>,
     pub charging: Option<ChargingStatus>,
+    /// Change of the battery level in percent per hour, negative while discharging
+    pub battery_rate: Option<f32>,
     pub muted: Option<bool>,
     pub mic_connected: Option<bool>,
     pub automatic_shutdown_after: Option<Duration>,
@@ -262,16 +756,20 @@ pub struct DeviceProperties {
     pub connected: Option<bool>,
     pub silent: Option<bool>,
     pub noise_gate_active: Option<bool>,
-    // Capability flags - set once during device initialization
-    pub can_set_mute: bool,
-    pub can_set_surround_sound: bool,
-    pub can_set_side_tone: bool,
-    pub can_set_automatic_shutdown: bool,
-    pub can_set_side_tone_volume: bool,
-    pub can_set_voice_prompt: bool,
-    pub can_set_silent_mode: bool,
-    pub can_set_equalizer: bool,
-    pub can_set_noise_gate: bool,
+    pub led_color: Option<Color>,
+    /// 0-100%
+    pub led_brightness: Option<u8>,
+    /// Equalizer slot stored on the headset that is currently selected
+    pub active_eq_slot: Option<u8>,
+    /// Quality of the wireless link between dongle and headset, 0-100%
+    pub link_quality: Option<u8>,
+    /// Device-native side tone volume range, None if unknown.
+    /// Negative values are sent as two's complement.
+    pub side_tone_volume_range: Option<RangeInclusive<i16>>,
+    /// Automatic shutdown delays in minutes the firmware accepts, None if any value works
+    pub automatic_shutdown_options: Option<&'static [u8]>,
+    /// Set once during device initialization
+    pub capabilities: Capabilities,
 }
 
 impl Display for DeviceProperties {
@@ -281,10 +779,16 @@ impl Display for DeviceProperties {
 }
 
 impl DeviceState {
-    pub fn new(product_ids: &[u16], vendor_ids: &[u16]) -> Result<Vec<Self>, DeviceError> {
-        let hid_api = HidApi::new()?;
+    /// Opens all attached devices matching the given ids.
+    /// If `serial_number` is set only devices with that serial number are considered.
+    pub fn new(
+        product_ids: &[u16],
+        vendor_ids: &[u16],
+        serial_number: Option<&str>,
+    ) -> Result<Vec<Self>, DeviceError> {
+        let hid_api = hid_api()?;
         let mut potential_devices = HashSet::new();
-        let mut error = Ok(());
+        let mut error = None;
         debug_println!(
             "Devices: {:?}",
             hid_api
@@ -293,20 +797,23 @@ impl DeviceState {
                 .map(|d| { (d.vendor_id(), d.product_id(), d.product_string()) })
                 .collect::<Vec<(u16, u16, Option<&str>)>>()
         );
-        let device_candidates: Vec<(HidDevice, u16, u16)> = hid_api
+        let device_candidates: Vec<DeviceState> = hid_api
             .device_list()
             .filter_map(|info| {
                 if product_ids.contains(&info.product_id())
                     && vendor_ids.contains(&info.vendor_id())
                 {
+                    if serial_number.is_some() && info.serial_number() != serial_number {
+                        return None;
+                    }
                     debug_println!(
                         "Selecting: {:x}:{:x} {:?}",
                         info.vendor_id(),
                         info.product_id(),
                         info.product_string()
                     );
-                    match info.open_device(&hid_api) {
-                        Ok(device) => Some((device, info.product_id(), info.vendor_id())),
+                    match Self::open(&hid_api, info) {
+                        Ok(state) => Some(state),
                         Err(e) => {
                             debug_println!(
                                 "Failed to open: {:x}:{:x} {:?}: {:?}",
@@ -315,7 +822,7 @@ impl DeviceState {
                                 info.product_string(),
                                 e
                             );
-                            error = Err(e);
+                            error = Some(e);
                             None
                         }
                     }
@@ -354,20 +861,77 @@ impl DeviceState {
                     if potential_devices.len() > 1 { "s" } else { "" }, names, if potential_devices.len() > 1 { "they are" } else { "it is" }
                 );
             }
-            error?;
-            return Err(DeviceError::NoDeviceFound());
+            return Err(error.unwrap_or(DeviceError::NoDeviceFound()));
         }
 
-        Ok(device_candidates
-            .into_iter()
-            .map(|(hid_device, product_id, vendor_id)| {
-                let device_name = hid_device.get_product_string().ok().flatten();
-                DeviceState {
-                    hid_device,
-                    device_properties: DeviceProperties::new(product_id, vendor_id, device_name),
-                }
-            })
-            .collect())
+        Ok(device_candidates)
+    }
+
+    /// Opens a single enumerated device.
+    /// A missing product string is not fatal, the device name will just be unknown.
+    fn open(hid_api: &HidApi, info: &DeviceInfo) -> Result<Self, DeviceError> {
+        let hid_device = info
+            .open_device(hid_api)
+            .map_err(|e| permission_error_or(e, DeviceError::OpenFailed))?;
+        let device_name = match Self::product_string(&hid_device) {
+            Ok(name) => Some(name),
+            Err(e) => {
+                debug_println!("{e}");
+                None
+            }
+        };
+        let mut state = Self::from_parts(Some(hid_device), info.vendor_id(), info.product_id());
+        state.interface_number = info.interface_number();
+        state.hid_path = Some(info.path().to_string_lossy().into_owned());
+        let device_properties = &mut state.device_properties;
+        device_properties.device_name = device_name;
+        device_properties.serial_number = info.serial_number().map(|s| s.to_string());
+        // bcdDevice, e.g. 0x0102 is version 1.02
+        let release = info.release_number();
+        device_properties.dongle_usb_release =
+            Some(format!("{:x}.{:02x}", release >> 8, release & 0xFF));
+        Ok(state)
+    }
+
+    /// A state for the given device without any known properties.
+    /// `hid_device` is None while decoding recorded reports, see `hid_log::replay`.
+    pub(crate) fn from_parts(
+        hid_device: Option<HidDevice>,
+        vendor_id: u16,
+        product_id: u16,
+    ) -> Self {
+        DeviceState {
+            hid_device,
+            interface_number: 0,
+            hid_path: None,
+            unanswered_queries: Vec::new(),
+            query_retries: 0,
+            batched_refresh: false,
+            charging_debounce: 1,
+            pending_charging: None,
+            battery_samples: Vec::new(),
+            battery_smoothing: false,
+            recent_battery_levels: Vec::new(),
+            button_sender: None,
+            device_properties: DeviceProperties::new(product_id, vendor_id, None),
+        }
+    }
+
+    /// Tells dongles apart by their serial number.
+    /// Dongles without one fall back to the HID path, so each of their interfaces counts on its own.
+    fn dongle_id(&self) -> Option<&str> {
+        self.device_properties
+            .serial_number
+            .as_deref()
+            .or(self.hid_path.as_deref())
+    }
+
+    fn product_string(hid_device: &HidDevice) -> Result<String, DeviceError> {
+        hid_device
+            .get_product_string()
+            .ok()
+            .flatten()
+            .ok_or(DeviceError::NoProductString())
     }
 
     /// Write a HID report to the device.
@@ -382,7 +946,13 @@ impl DeviceState {
     /// Adapted from PR #20 by @navrozashvili
     /// Source: https://github.com/LennardKittner/HyperHeadset/pull/20
     pub fn write_hid_report(&self, packet: &[u8]) -> Result<(), HidError> {
-        match self.hid_device.write(packet) {
+        let Some(hid_device) = &self.hid_device else {
+            return Err(HidError::HidApiError {
+                message: "Cannot write while replaying a recording".to_string(),
+            });
+        };
+        hid_log::record("write", packet);
+        match hid_device.write(packet) {
             Ok(_) => Ok(()),
             Err(write_err) => {
                 #[cfg(target_os = "windows")]
@@ -395,7 +965,7 @@ impl DeviceState {
                         {
                             // If the feature report also fails, prefer returning the original
                             // write() error since that's what callers attempted.
-                            if let Err(_feature_err) = self.hid_device.send_feature_report(packet) {
+                            if let Err(_feature_err) = hid_device.send_feature_report(packet) {
                                 return Err(write_err);
                             }
                             return Ok(());
@@ -407,10 +977,65 @@ impl DeviceState {
         }
     }
 
+    /// Accepts a changed charging status only after `charging_debounce` consecutive readings,
+    /// so a wiggled cable does not cause the status to flip.
+    fn update_charging(&mut self, status: ChargingStatus) {
+        let accepted = self.device_properties.charging;
+        if accepted.is_none() || accepted == Some(status) {
+            self.pending_charging = None;
+            self.device_properties.charging = Some(status);
+            return;
+        }
+        let count = match self.pending_charging {
+            Some((pending, count)) if pending == status => count + 1,
+            _ => 1,
+        };
+        if count >= self.charging_debounce {
+            self.pending_charging = None;
+            self.device_properties.charging = Some(status);
+            // the rate changes direction, old samples would skew it
+            self.battery_samples.clear();
+            self.device_properties.battery_rate = None;
+        } else {
+            self.pending_charging = Some((status, count));
+        }
+    }
+
+    fn update_battery_level(&mut self, level: u8) {
+        if self.recent_battery_levels.len() >= BATTERY_SMOOTHING_READINGS {
+            self.recent_battery_levels.remove(0);
+        }
+        self.recent_battery_levels.push(level);
+        let accept = match self.device_properties.battery_level {
+            Some(shown) if self.battery_smoothing => {
+                shown.abs_diff(level) >= BATTERY_SMOOTHING_THRESHOLD
+                    || (self.recent_battery_levels.len() == BATTERY_SMOOTHING_READINGS
+                        && self.recent_battery_levels.iter().all(|l| *l == level))
+            }
+            _ => true,
+        };
+        if accept {
+            self.device_properties.battery_level = Some(level);
+        }
+
+        // the rate uses the raw readings, the noise averages out over the window
+        let now = Instant::now();
+        self.battery_samples
+            .retain(|(time, _)| now.duration_since(*time) <= BATTERY_RATE_WINDOW);
+        self.battery_samples.push((now, level));
+        let (first_time, first_level) = self.battery_samples[0];
+        let span = now.duration_since(first_time);
+        self.device_properties.battery_rate = if span >= BATTERY_RATE_MIN_SPAN {
+            Some((level as f32 - first_level as f32) * 3600.0 / span.as_secs_f32())
+        } else {
+            None
+        };
+    }
+
     fn update_self_with_event(&mut self, event: &DeviceEvent) {
         match event {
-            DeviceEvent::BatterLevel(level) => self.device_properties.battery_level = Some(*level),
-            DeviceEvent::Charging(status) => self.device_properties.charging = Some(*status),
+            DeviceEvent::BatterLevel(level) => self.update_battery_level(*level),
+            DeviceEvent::Charging(status) => self.update_charging(*status),
             DeviceEvent::Muted(status) => self.device_properties.muted = Some(*status),
             DeviceEvent::MicConnected(status) => {
                 self.device_properties.mic_connected = Some(*status)
@@ -438,24 +1063,83 @@ impl DeviceState {
             DeviceEvent::NoiseGateActive(on) => {
                 self.device_properties.noise_gate_active = Some(*on)
             }
+            DeviceEvent::LedState(color, brightness) => {
+                self.device_properties.led_color = Some(*color);
+                self.device_properties.led_brightness = Some(*brightness);
+            }
+            DeviceEvent::LedBrightness(brightness) => {
+                self.device_properties.led_brightness = Some(*brightness)
+            }
+            DeviceEvent::ActiveEqSlot(slot) => self.device_properties.active_eq_slot = Some(*slot),
+            DeviceEvent::LinkQuality(quality) => {
+                self.device_properties.link_quality = Some(*quality)
+            }
+            DeviceEvent::ButtonPress(button) => {
+                debug_println!("Button pressed: {button:?}");
+                if let Some(sender) = &self.button_sender {
+                    let _ = sender.send(*button);
+                }
+            }
+            DeviceEvent::Equalizer(_) => (),
         };
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum PropertyType {
+    /// A setting this device only reports, it is shown with a "(read-only)" marker
     ReadOnly,
+    /// A status that no device can change, e.g. the battery level
     AlwaysReadOnly,
     ReadWrite,
 }
 
 #[derive(Debug)]
 pub enum PropertyDescriptorWrapper {
-    Int(PropertyDescriptor<u8>, &'static [u8]),
+    Int(PropertyDescriptor<u8>, IntOptions),
     Bool(PropertyDescriptor<bool>),
     String(PropertyDescriptor<String>),
 }
 
+/// The values of an integer property that are offered in the tray, empty if it can only be shown
+#[derive(Debug, Clone, Default)]
+pub struct IntOptions {
+    pub values: Vec<u8>,
+    /// The values are two's complement, e.g. 251 is -5
+    pub signed: bool,
+}
+
+impl IntOptions {
+    pub fn unsigned(values: &[u8]) -> Self {
+        IntOptions {
+            values: values.to_vec(),
+            signed: false,
+        }
+    }
+
+    /// Every value of a device-native range, or about ten evenly spaced ones for larger ranges
+    pub fn from_range(range: &RangeInclusive<i16>) -> Self {
+        let step = ((range.end() - range.start()) as usize).div_ceil(10).max(1);
+        IntOptions {
+            values: range
+                .clone()
+                .step_by(step)
+                .map(|value| value as u8)
+                .collect(),
+            signed: *range.start() < 0,
+        }
+    }
+
+    /// Formats a value of this property like `format_int_value`, signed values keep their sign
+    pub fn format(&self, value: u8, suffix: &str) -> String {
+        if self.signed {
+            format!("{}{}", value as i8, suffix)
+        } else {
+            format_int_value(value, suffix)
+        }
+    }
+}
+
 pub struct PropertyDescriptor<T: 'static> {
     pub name: &'static str,
     pub pretty_name: &'static str,
@@ -482,8 +1166,11 @@ impl DeviceProperties {
             product_id,
             vendor_id,
             device_name,
+            serial_number: None,
+            dongle_usb_release: None,
             battery_level: None,
             charging: None,
+            battery_rate: None,
             muted: None,
             mic_connected: None,
             automatic_shutdown_after: None,
@@ -496,16 +1183,42 @@ impl DeviceProperties {
             connected: None,
             silent: None,
             noise_gate_active: None,
-            can_set_mute: false,
-            can_set_surround_sound: false,
-            can_set_side_tone: false,
-            can_set_automatic_shutdown: false,
-            can_set_side_tone_volume: false,
-            can_set_voice_prompt: false,
-            can_set_silent_mode: false,
-            can_set_equalizer: false,
-            can_set_noise_gate: false,
+            led_color: None,
+            led_brightness: None,
+            active_eq_slot: None,
+            link_quality: None,
+            side_tone_volume_range: None,
+            automatic_shutdown_options: None,
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    /// The side tone volume mapped from the device-native range to 0-100%
+    pub fn side_tone_volume_percent(&self) -> Option<u8> {
+        let range = self.side_tone_volume_range.as_ref()?;
+        let raw = self.side_tone_volume?;
+        let native = native_side_tone_volume(raw, range);
+        let span = (range.end() - range.start()).max(1) as i32;
+        let percent =
+            (native.clamp(*range.start(), *range.end()) - range.start()) as i32 * 100 / span;
+        Some(percent as u8)
+    }
+
+    /// The link quality as 0-3 bars
+    pub fn link_quality_bars(&self) -> Option<u8> {
+        let quality = self.link_quality?.min(100) as u16;
+        Some(((quality * 3 + 50) / 100) as u8)
+    }
+
+    /// Rough time until the battery is full, based on the battery rate while charging.
+    /// None if the headset is not charging or the rate is not known yet.
+    pub fn time_to_full(&self) -> Option<Duration> {
+        if self.charging != Some(ChargingStatus::Charging) {
+            return None;
         }
+        let rate = self.battery_rate.filter(|rate| *rate > 0.0)?;
+        let missing = 100u8.saturating_sub(self.battery_level?);
+        Some(Duration::from_secs_f32(missing as f32 / rate * 3600.0))
     }
 
     pub fn get_properties(&self) -> Vec<PropertyDescriptorWrapper> {
@@ -527,14 +1240,32 @@ impl DeviceProperties {
                     property_type: PropertyType::AlwaysReadOnly,
                     create_event: &|_| None,
                 },
-                &[],
+                IntOptions::default(),
             ),
+            PropertyDescriptorWrapper::String(PropertyDescriptor {
+                name: "battery_rate",
+                pretty_name: "Battery rate",
+                data: self.battery_rate.map(|rate| format!("{rate:+.1}")),
+                suffix: "%/h",
+                property_type: PropertyType::AlwaysReadOnly,
+                create_event: &|_| None,
+            }),
+            PropertyDescriptorWrapper::String(PropertyDescriptor {
+                name: "time_to_full",
+                pretty_name: "Time to full",
+                data: self
+                    .time_to_full()
+                    .map(|time| format!("~{}", time.as_secs().div_ceil(60))),
+                suffix: "min",
+                property_type: PropertyType::AlwaysReadOnly,
+                create_event: &|_| None,
+            }),
             PropertyDescriptorWrapper::Bool(PropertyDescriptor {
                 name: "mic_muted",
                 pretty_name: "Muted",
                 data: self.muted,
                 suffix: "",
-                property_type: if self.can_set_mute {
+                property_type: if self.capabilities.can_set_mute {
                     PropertyType::ReadWrite
                 } else {
                     PropertyType::ReadOnly
@@ -555,9 +1286,9 @@ impl DeviceProperties {
                     pretty_name: "Automatic shutdown after",
                     data: self
                         .automatic_shutdown_after
-                        .map(|t| (t.as_secs() / 60) as u8),
+                        .map(|t| (t.as_secs() / 60).min(u8::MAX as u64) as u8),
                     suffix: "min",
-                    property_type: if self.can_set_mute {
+                    property_type: if self.capabilities.can_set_automatic_shutdown {
                         PropertyType::ReadWrite
                     } else {
                         PropertyType::ReadOnly
@@ -568,7 +1299,10 @@ impl DeviceProperties {
                         )))
                     },
                 },
-                &[0, 5, 10, 15, 20, 30, 40, 60],
+                IntOptions::unsigned(
+                    self.automatic_shutdown_options
+                        .unwrap_or(&[0, 5, 10, 15, 20, 30, 40, 60]),
+                ),
             ),
             PropertyDescriptorWrapper::Int(
                 PropertyDescriptor {
@@ -579,7 +1313,7 @@ impl DeviceProperties {
                     property_type: PropertyType::AlwaysReadOnly,
                     create_event: &|_| None,
                 },
-                &[],
+                IntOptions::default(),
             ),
             PropertyDescriptorWrapper::String(PropertyDescriptor {
                 name: "product_color",
@@ -594,7 +1328,7 @@ impl DeviceProperties {
                 pretty_name: "Side tone",
                 data: self.side_tone_on,
                 suffix: "",
-                property_type: if self.can_set_side_tone {
+                property_type: if self.capabilities.can_set_side_tone {
                     PropertyType::ReadWrite
                 } else {
                     PropertyType::ReadOnly
@@ -607,21 +1341,42 @@ impl DeviceProperties {
                     pretty_name: "Side tone volume",
                     data: self.side_tone_volume,
                     suffix: "",
-                    property_type: if self.can_set_side_tone_volume {
+                    property_type: if self.capabilities.can_set_side_tone_volume {
                         PropertyType::ReadWrite
                     } else {
                         PropertyType::ReadOnly
                     },
                     create_event: &|v| Some(DeviceEvent::SideToneVolume(v)),
                 },
-                &[0, 25, 50, 75, 100, 125, 150, 175, 200, 225, 250],
+                match &self.side_tone_volume_range {
+                    Some(range) => IntOptions::from_range(range),
+                    None => {
+                        IntOptions::unsigned(&[0, 25, 50, 75, 100, 125, 150, 175, 200, 225, 250])
+                    }
+                },
+            ),
+            PropertyDescriptorWrapper::Int(
+                PropertyDescriptor {
+                    name: "side_tone_volume_percent",
+                    pretty_name: "Side tone volume level",
+                    data: self.side_tone_volume_percent(),
+                    suffix: "%",
+                    // only set through --side_tone_volume_percent, but marked like the raw volume
+                    property_type: if self.capabilities.can_set_side_tone_volume {
+                        PropertyType::AlwaysReadOnly
+                    } else {
+                        PropertyType::ReadOnly
+                    },
+                    create_event: &|_| None,
+                },
+                IntOptions::default(),
             ),
             PropertyDescriptorWrapper::Bool(PropertyDescriptor {
                 name: "surround_sound_enabled",
                 pretty_name: "Surround sound",
                 data: self.surround_sound,
                 suffix: "",
-                property_type: if self.can_set_surround_sound {
+                property_type: if self.capabilities.can_set_surround_sound {
                     PropertyType::ReadWrite
                 } else {
                     PropertyType::ReadOnly
@@ -633,7 +1388,7 @@ impl DeviceProperties {
                 pretty_name: "Voice prompt",
                 data: self.voice_prompt_on,
                 suffix: "",
-                property_type: if self.can_set_voice_prompt {
+                property_type: if self.capabilities.can_set_voice_prompt {
                     PropertyType::ReadWrite
                 } else {
                     PropertyType::ReadOnly
@@ -645,7 +1400,7 @@ impl DeviceProperties {
                 pretty_name: "Playback muted",
                 data: self.silent,
                 suffix: "",
-                property_type: if self.can_set_silent_mode {
+                property_type: if self.capabilities.can_set_silent_mode {
                     PropertyType::ReadWrite
                 } else {
                     PropertyType::ReadOnly
@@ -657,12 +1412,63 @@ impl DeviceProperties {
                 pretty_name: "Noise gate active",
                 data: self.noise_gate_active,
                 suffix: "",
-                property_type: if self.can_set_noise_gate {
+                property_type: if self.capabilities.can_set_noise_gate {
+                    PropertyType::ReadWrite
+                } else {
+                    PropertyType::ReadOnly
+                },
+                create_event: &move |enable| Some(DeviceEvent::NoiseGateActive(enable)),
+            }),
+            PropertyDescriptorWrapper::Int(
+                PropertyDescriptor {
+                    name: "active_eq_slot",
+                    pretty_name: "Equalizer slot",
+                    data: self.active_eq_slot,
+                    suffix: "",
+                    property_type: if self.capabilities.can_set_active_eq_slot {
+                        PropertyType::ReadWrite
+                    } else {
+                        PropertyType::ReadOnly
+                    },
+                    create_event: &|slot| Some(DeviceEvent::ActiveEqSlot(slot)),
+                },
+                IntOptions::unsigned(&[1, 2, 3]),
+            ),
+            // there is no way to enter a color in the tray, it is changed together with the brightness
+            PropertyDescriptorWrapper::String(PropertyDescriptor {
+                name: "led_color",
+                pretty_name: "LED color",
+                data: self.led_color.map(|color| color.to_string()),
+                suffix: "",
+                property_type: if self.capabilities.can_set_led {
                     PropertyType::ReadWrite
                 } else {
                     PropertyType::ReadOnly
                 },
-                create_event: &move |enable| Some(DeviceEvent::NoiseGateActive(enable)),
+                create_event: &|_| None,
+            }),
+            PropertyDescriptorWrapper::Int(
+                PropertyDescriptor {
+                    name: "led_brightness",
+                    pretty_name: "LED brightness",
+                    data: self.led_brightness,
+                    suffix: "%",
+                    property_type: if self.capabilities.can_set_led {
+                        PropertyType::ReadWrite
+                    } else {
+                        PropertyType::ReadOnly
+                    },
+                    create_event: &|brightness| Some(DeviceEvent::LedBrightness(brightness)),
+                },
+                IntOptions::unsigned(&[0, 25, 50, 75, 100]),
+            ),
+            PropertyDescriptorWrapper::String(PropertyDescriptor {
+                name: "dongle_usb_release",
+                pretty_name: "Dongle USB release",
+                data: self.dongle_usb_release.clone(),
+                suffix: "",
+                property_type: PropertyType::AlwaysReadOnly,
+                create_event: &|_| None,
             }),
             PropertyDescriptorWrapper::Bool(PropertyDescriptor {
                 name: "connected",
@@ -672,6 +1478,17 @@ impl DeviceProperties {
                 property_type: PropertyType::AlwaysReadOnly,
                 create_event: &|_| None,
             }),
+            PropertyDescriptorWrapper::Int(
+                PropertyDescriptor {
+                    name: "link_quality",
+                    pretty_name: "Link quality",
+                    data: self.link_quality,
+                    suffix: LINK_QUALITY_SUFFIXES[self.link_quality_bars().unwrap_or(0) as usize],
+                    property_type: PropertyType::AlwaysReadOnly,
+                    create_event: &|_| None,
+                },
+                IntOptions::default(),
+            ),
         ]
     }
 
@@ -680,11 +1497,11 @@ impl DeviceProperties {
             .iter()
             .filter_map(|prop| {
                 let (name, data, suffix) = match prop {
-                    PropertyDescriptorWrapper::Int(property_descriptor, _) => (
+                    PropertyDescriptorWrapper::Int(property_descriptor, options) => (
                         property_descriptor.pretty_name,
                         &property_descriptor
                             .data
-                            .map(|v| format_int_value(v, property_descriptor.suffix)),
+                            .map(|v| options.format(v, property_descriptor.suffix)),
                         "",
                     ),
                     PropertyDescriptorWrapper::Bool(property_descriptor) => (
@@ -705,16 +1522,53 @@ impl DeviceProperties {
             .join("\n")
     }
 
+    /// All known properties as `"name": value` JSON fields
+    pub fn to_json_fields(&self) -> Vec<String> {
+        self.get_properties()
+            .iter()
+            .filter_map(|property| match property {
+                PropertyDescriptorWrapper::Int(property_descriptor, _items) => property_descriptor
+                    .data
+                    .map(|data| format!("\"{}\": {}", property_descriptor.name, data)),
+                PropertyDescriptorWrapper::Bool(property_descriptor) => property_descriptor
+                    .data
+                    .map(|data| format!("\"{}\": {}", property_descriptor.name, data)),
+                // shown with a sign or as an estimate for humans, but plain numbers in JSON
+                PropertyDescriptorWrapper::String(property_descriptor)
+                    if property_descriptor.name == "battery_rate" =>
+                {
+                    self.battery_rate
+                        .map(|rate| format!("\"{}\": {rate:.1}", property_descriptor.name))
+                }
+                PropertyDescriptorWrapper::String(property_descriptor)
+                    if property_descriptor.name == "time_to_full" =>
+                {
+                    self.time_to_full().map(|time| {
+                        format!(
+                            "\"{}\": {}",
+                            property_descriptor.name,
+                            time.as_secs().div_ceil(60)
+                        )
+                    })
+                }
+                PropertyDescriptorWrapper::String(property_descriptor) => property_descriptor
+                    .data
+                    .as_ref()
+                    .map(|data| format!("\"{}\": {}", property_descriptor.name, json_string(data))),
+            })
+            .collect()
+    }
+
     pub fn to_string_with_readonly_info(&self, padding: usize) -> String {
         self.get_properties()
             .iter()
             .filter_map(|prop| {
                 let (name, data, suffix, property_type) = match prop {
-                    PropertyDescriptorWrapper::Int(property_descriptor, _) => (
+                    PropertyDescriptorWrapper::Int(property_descriptor, options) => (
                         property_descriptor.pretty_name,
                         &property_descriptor
                             .data
-                            .map(|v| format_int_value(v, property_descriptor.suffix)),
+                            .map(|v| options.format(v, property_descriptor.suffix)),
                         "",
                         property_descriptor.property_type,
                     ),
@@ -758,10 +1612,22 @@ pub enum DeviceError {
     HidError(#[from] HidError),
     #[termination(msg("No device found."))]
     NoDeviceFound(),
+    #[termination(msg(
+        "Found a compatible device but failed to open it: {0:?}. Are the permissions set up correctly?"
+    ))]
+    OpenFailed(HidError),
+    #[termination(msg(
+        "Permission denied while opening the device: {0}. On Linux, create /etc/udev/rules.d/99-HyperHeadset.rules as described in the README and reconnect the dongle."
+    ))]
+    PermissionDenied(String),
+    #[termination(msg("Failed to read the product string of the device."))]
+    NoProductString(),
     #[termination(msg("No response. Is the headset turned on?"))]
     HeadSetOff(),
     #[termination(msg("No response."))]
     NoResponse(),
+    #[termination(msg("This feature is not supported by the device."))]
+    NotSupported(),
     #[termination(msg("Unknown response: {0:?} with length: {1:?}"))]
     UnknownResponse([u8; 8], usize),
 }
@@ -783,6 +1649,130 @@ pub enum DeviceEvent {
     Silent(bool),
     RequireSIRKReset(bool),
     NoiseGateActive(bool),
+    ButtonPress(Button),
+    /// LED color and brightness in percent
+    LedState(Color, u8),
+    /// LED brightness in percent, only sent to the device. The current color is kept.
+    LedBrightness(u8),
+    /// All equalizer bands in dB, only sent to the device since it does not report them
+    Equalizer([f32; NUM_BANDS]),
+    /// Equalizer slot stored on the headset, e.g. selected with a button.
+    /// Unlike `Equalizer` the bands of the slot are kept by the headset.
+    ActiveEqSlot(u8),
+    /// Quality of the wireless link, 0-100%. Drivers scale the raw value of the dongle.
+    LinkQuality(u8),
+}
+
+/// Media buttons on the headset
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Button {
+    VolumeUp,
+    VolumeDown,
+    PlayPause,
+}
+
+fn color_to_json(color: &Color) -> String {
+    match color {
+        Color::BlackBlack => "\"black_black\"".to_string(),
+        Color::WhiteWhite => "\"white_white\"".to_string(),
+        Color::BlackRed => "\"black_red\"".to_string(),
+        Color::UnknownColor(n) => format!("\"unknown_{n}\""),
+        Color::Rgb(r, g, b) => format!("\"#{r:02x}{g:02x}{b:02x}\""),
+    }
+}
+
+impl DeviceEvent {
+    /// The name of the query in `Device::get_named_query_packets` this event answers
+    fn query_name(&self) -> Option<&'static str> {
+        match self {
+            DeviceEvent::WirelessConnected(_) => Some("connected"),
+            DeviceEvent::Charging(_) => Some("charging_status"),
+            DeviceEvent::BatterLevel(_) => Some("battery_level"),
+            DeviceEvent::AutomaticShutdownAfter(_) => Some("automatic_shutdown_interval"),
+            DeviceEvent::Muted(_) => Some("mic_muted"),
+            DeviceEvent::SurroundSound(_) => Some("surround_sound_enabled"),
+            DeviceEvent::MicConnected(_) => Some("mic_connected"),
+            DeviceEvent::PairingInfo(_) => Some("pairing_info"),
+            DeviceEvent::ProductColor(_) => Some("product_color"),
+            DeviceEvent::SideToneOn(_) => Some("side_tone_enabled"),
+            DeviceEvent::SideToneVolume(_) => Some("side_tone_volume"),
+            DeviceEvent::VoicePrompt(_) => Some("voice_prompt_enabled"),
+            DeviceEvent::RequireSIRKReset(_) => Some("sirk"),
+            DeviceEvent::Silent(_) => Some("playback_muted"),
+            DeviceEvent::NoiseGateActive(_) => Some("noise_gate_enabled"),
+            DeviceEvent::LedState(..) | DeviceEvent::LedBrightness(_) => Some("led"),
+            DeviceEvent::ActiveEqSlot(_) => Some("active_eq_slot"),
+            DeviceEvent::LinkQuality(_) => Some("link_quality"),
+            DeviceEvent::ButtonPress(_) | DeviceEvent::Equalizer(_) => None,
+        }
+    }
+
+    /// A stable JSON representation of the form `{"type": "battery_level", "value": 80}`.
+    /// Durations are given in seconds, all other non-numeric values as snake_case strings.
+    pub fn to_json(&self) -> String {
+        let (event_type, value) = match self {
+            DeviceEvent::BatterLevel(level) => ("battery_level", level.to_string()),
+            DeviceEvent::Muted(muted) => ("muted", muted.to_string()),
+            DeviceEvent::MicConnected(connected) => ("mic_connected", connected.to_string()),
+            DeviceEvent::Charging(status) => (
+                "charging",
+                match status {
+                    ChargingStatus::NotCharging => "\"not_charging\"".to_string(),
+                    ChargingStatus::Charging => "\"charging\"".to_string(),
+                    ChargingStatus::FullyCharged => "\"fully_charged\"".to_string(),
+                    ChargingStatus::Unknown(status) => format!("\"unknown_{status}\""),
+                },
+            ),
+            DeviceEvent::AutomaticShutdownAfter(duration) => {
+                ("automatic_shutdown_after", duration.as_secs().to_string())
+            }
+            DeviceEvent::PairingInfo(info) => ("pairing_info", info.to_string()),
+            DeviceEvent::ProductColor(color) => ("product_color", color_to_json(color)),
+            DeviceEvent::SideToneOn(on) => ("side_tone_on", on.to_string()),
+            DeviceEvent::SideToneVolume(volume) => ("side_tone_volume", volume.to_string()),
+            DeviceEvent::VoicePrompt(on) => ("voice_prompt", on.to_string()),
+            DeviceEvent::WirelessConnected(connected) => {
+                ("wireless_connected", connected.to_string())
+            }
+            DeviceEvent::SurroundSound(on) => ("surround_sound", on.to_string()),
+            DeviceEvent::Silent(silent) => ("silent", silent.to_string()),
+            DeviceEvent::RequireSIRKReset(reset) => ("require_sirk_reset", reset.to_string()),
+            DeviceEvent::NoiseGateActive(active) => ("noise_gate_active", active.to_string()),
+            DeviceEvent::ButtonPress(button) => (
+                "button_press",
+                format!(
+                    "\"{}\"",
+                    match button {
+                        Button::VolumeUp => "volume_up",
+                        Button::VolumeDown => "volume_down",
+                        Button::PlayPause => "play_pause",
+                    }
+                ),
+            ),
+            DeviceEvent::ActiveEqSlot(slot) => ("active_eq_slot", slot.to_string()),
+            DeviceEvent::LinkQuality(quality) => ("link_quality", quality.to_string()),
+            DeviceEvent::LedBrightness(brightness) => ("led_brightness", brightness.to_string()),
+            DeviceEvent::LedState(color, brightness) => (
+                "led_state",
+                format!(
+                    "{{\"color\": {}, \"brightness\": {brightness}}}",
+                    color_to_json(color)
+                ),
+            ),
+            DeviceEvent::Equalizer(bands) => (
+                "equalizer",
+                format!(
+                    "[{}]",
+                    bands
+                        .iter()
+                        .map(|band| band.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ),
+        };
+        format!("{{\"type\": \"{event_type}\", \"value\": {value}}}")
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -791,6 +1781,7 @@ pub enum Color {
     WhiteWhite,
     BlackRed,
     UnknownColor(u8),
+    Rgb(u8, u8, u8),
 }
 
 impl Display for Color {
@@ -803,6 +1794,7 @@ impl Display for Color {
                 Color::WhiteWhite => "White".to_string(),
                 Color::BlackRed => "Red".to_string(),
                 Color::UnknownColor(n) => format!("Unknown color {}", n),
+                Color::Rgb(r, g, b) => format!("#{r:02X}{g:02X}{b:02X}"),
             }
         )
     }
@@ -819,12 +1811,19 @@ impl From<u8> for Color {
     }
 }
 
+impl From<[u8; 3]> for Color {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Color::Rgb(r, g, b)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ChargingStatus {
     NotCharging,
     Charging,
     FullyCharged,
-    ChargeError,
+    /// A status byte that is not decoded yet
+    Unknown(u8),
 }
 
 impl Display for ChargingStatus {
@@ -833,10 +1832,10 @@ impl Display for ChargingStatus {
             f,
             "{}",
             match self {
-                ChargingStatus::NotCharging => "Not charging",
-                ChargingStatus::Charging => "Charging",
-                ChargingStatus::FullyCharged => "Fully charged",
-                ChargingStatus::ChargeError => "Charging error!",
+                ChargingStatus::NotCharging => "Not charging".to_string(),
+                ChargingStatus::Charging => "Charging".to_string(),
+                ChargingStatus::FullyCharged => "Fully charged".to_string(),
+                ChargingStatus::Unknown(status) => format!("Unknown ({status})"),
             }
         )
     }
@@ -848,7 +1847,8 @@ impl From<u8> for ChargingStatus {
             0 => ChargingStatus::NotCharging,
             1 => ChargingStatus::Charging,
             2 => ChargingStatus::FullyCharged,
-            _ => ChargingStatus::ChargeError,
+            // no driver knows an error code, so other values are not treated as errors
+            status => ChargingStatus::Unknown(status),
         }
     }
 }
@@ -884,73 +1884,175 @@ pub trait Device {
     fn set_equalizer_band_packet(&self, _band_index: u8, _db_value: f32) -> Option<Vec<u8>> {
         None
     }
+    /// Set multiple equalizer bands at once, given as (band_index, dB value) pairs.
+    /// Returns one packet per band, or None if any band cannot be set.
+    fn set_equalizer_bands_packets(&self, bands: &[(u8, f32)]) -> Option<Vec<Vec<u8>>> {
+        bands
+            .iter()
+            .map(|(band_index, db_value)| self.set_equalizer_band_packet(*band_index, *db_value))
+            .collect()
+    }
     fn get_noise_gate_packet(&self) -> Option<Vec<u8>> {
         None
     }
     fn set_noise_gate_packet(&self, _enable: bool) -> Option<Vec<u8>> {
         None
     }
+    fn get_led_packet(&self) -> Option<Vec<u8>> {
+        None
+    }
+    /// Set the LED color and brightness, 0-100%
+    fn set_led_packet(&self, _color: Color, _brightness: u8) -> Option<Vec<u8>> {
+        None
+    }
+    /// Query which equalizer slot stored on the headset is selected
+    fn get_active_eq_slot_packet(&self) -> Option<Vec<u8>> {
+        None
+    }
+    /// Select an equalizer slot stored on the headset
+    fn set_active_eq_slot_packet(&self, _slot: u8) -> Option<Vec<u8>> {
+        None
+    }
+    /// Query the quality of the wireless link, answered with `DeviceEvent::LinkQuality`
+    fn get_link_quality_packet(&self) -> Option<Vec<u8>> {
+        None
+    }
     fn get_event_from_device_response(&self, response: &[u8]) -> Option<Vec<DeviceEvent>>;
+    /// Name of the implementation handling the device, e.g. "CloudIIIWireless"
+    fn model_name(&self) -> &'static str;
     fn get_device_state(&self) -> &DeviceState;
+    /// Time the device needs after a change before it reports the new value
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(500)
+    }
+    /// The HID interface the device listens on for commands.
+    /// Some devices expose multiple interfaces and only one of them responds to our packets.
+    /// None means any interface can be used.
+    fn expected_interface_number(&self) -> Option<i32> {
+        None
+    }
     fn get_device_state_mut(&mut self) -> &mut DeviceState;
     fn prepare_write(&mut self) {}
+    /// Prepares the device for writing and writes a single packet to it
+    fn write_packet(&mut self, packet: &[u8]) -> Result<(), HidError> {
+        self.prepare_write();
+        self.get_device_state().write_hid_report(packet)
+    }
+
+    /// Sets all equalizer bands, given in dB from 32Hz to 16kHz.
+    /// The devices do not report their equalizer settings, so success only means all packets were written.
+    fn apply_eq_preset(&mut self, bands: &[f32; NUM_BANDS]) -> Result<(), DeviceError> {
+        let bands: Vec<(u8, f32)> = bands
+            .iter()
+            .enumerate()
+            .map(|(band_index, db_value)| (band_index as u8, *db_value))
+            .collect();
+        let packets = self
+            .set_equalizer_bands_packets(&bands)
+            .ok_or(DeviceError::NotSupported())?;
+        for packet in packets {
+            debug_println!("Write packet: {packet:?}");
+            self.write_packet(&packet)?;
+            std::thread::sleep(RESPONSE_DELAY);
+        }
+        Ok(())
+    }
     /// whether the app should periodically listen for packets from the headsets
     fn allow_passive_refresh(&mut self) -> bool;
 
+    /// What this device supports, determined by building every set packet.
+    /// Only used once by `init_capabilities`, use `capabilities` instead.
+    fn detect_capabilities(&self) -> Capabilities {
+        Capabilities {
+            can_set_mute: self.set_mute_packet(false).is_some(),
+            can_set_surround_sound: self.set_surround_sound_packet(false).is_some(),
+            can_set_side_tone: self.set_side_tone_packet(false).is_some(),
+            can_set_automatic_shutdown: self
+                .set_automatic_shut_down_packet(Duration::from_secs(0))
+                .is_some(),
+            can_set_side_tone_volume: self.set_side_tone_volume_packet(0).is_some(),
+            can_set_voice_prompt: self.set_voice_prompt_packet(false).is_some(),
+            can_set_silent_mode: self.set_silent_mode_packet(false).is_some(),
+            can_set_equalizer: self.set_equalizer_bands_packets(&[(0, 0.0)]).is_some(),
+            can_set_noise_gate: self.set_noise_gate_packet(true).is_some(),
+            can_set_led: self
+                .set_led_packet(Color::Rgb(255, 255, 255), 100)
+                .is_some(),
+            can_set_active_eq_slot: self.set_active_eq_slot_packet(1).is_some(),
+        }
+    }
+
     // Helper methods to check if features are writable
+    /// What this device supports, stored by `init_capabilities` when the device is connected
+    fn capabilities(&self) -> Capabilities {
+        self.get_device_state().device_properties.capabilities
+    }
     fn can_set_mute(&self) -> bool {
-        self.set_mute_packet(false).is_some()
+        self.capabilities().can_set_mute
     }
     fn can_set_surround_sound(&self) -> bool {
-        self.set_surround_sound_packet(false).is_some()
+        self.capabilities().can_set_surround_sound
     }
     fn can_set_side_tone(&self) -> bool {
-        self.set_side_tone_packet(false).is_some()
+        self.capabilities().can_set_side_tone
     }
     fn can_set_automatic_shutdown(&self) -> bool {
-        self.set_automatic_shut_down_packet(Duration::from_secs(0))
-            .is_some()
+        self.capabilities().can_set_automatic_shutdown
     }
     fn can_set_side_tone_volume(&self) -> bool {
-        self.set_side_tone_volume_packet(0).is_some()
+        self.capabilities().can_set_side_tone_volume
     }
     fn can_set_voice_prompt(&self) -> bool {
-        self.set_voice_prompt_packet(false).is_some()
+        self.capabilities().can_set_voice_prompt
     }
     fn can_set_silent_mode(&self) -> bool {
-        self.set_silent_mode_packet(false).is_some()
+        self.capabilities().can_set_silent_mode
     }
     fn can_set_equalizer(&self) -> bool {
-        self.set_equalizer_band_packet(0, 0.0).is_some()
+        self.capabilities().can_set_equalizer
     }
     fn can_set_noise_gate(&self) -> bool {
-        self.set_noise_gate_packet(true).is_some()
+        self.capabilities().can_set_noise_gate
+    }
+    fn can_set_led(&self) -> bool {
+        self.capabilities().can_set_led
+    }
+    fn can_set_active_eq_slot(&self) -> bool {
+        self.capabilities().can_set_active_eq_slot
+    }
+    /// The device-native side tone volume range. Negative values are sent as two's complement.
+    /// None if unknown.
+    fn side_tone_volume_range(&self) -> Option<RangeInclusive<i16>> {
+        None
+    }
+    /// The only automatic shutdown delays in minutes the firmware accepts.
+    /// None if any value works.
+    fn automatic_shutdown_options(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    /// Which properties can be read and written and the values the device accepts
+    fn supported_features(&self) -> FeatureSet {
+        FeatureSet {
+            capabilities: self.detect_capabilities(),
+            readable: self
+                .get_named_query_packets()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+            side_tone_volume_range: self.side_tone_volume_range(),
+            automatic_shutdown_options: self.automatic_shutdown_options(),
+        }
     }
 
-    // Initialize capability flags in device state
+    // Initialize capability flags in device state, they are kept by `clear_state`
     fn init_capabilities(&mut self) {
-        // Collect capabilities first to avoid borrowing conflicts
-        let can_set_mute = self.can_set_mute();
-        let can_set_surround_sound = self.can_set_surround_sound();
-        let can_set_side_tone = self.can_set_side_tone();
-        let can_set_automatic_shutdown = self.can_set_automatic_shutdown();
-        let can_set_side_tone_volume = self.can_set_side_tone_volume();
-        let can_set_voice_prompt = self.can_set_voice_prompt();
-        let can_set_silent_mode = self.can_set_silent_mode();
-        let can_set_equalizer = self.can_set_equalizer();
-        let can_set_noise_gate = self.can_set_noise_gate();
-
-        // Now set them in device state
-        let state = self.get_device_state_mut();
-        state.device_properties.can_set_mute = can_set_mute;
-        state.device_properties.can_set_surround_sound = can_set_surround_sound;
-        state.device_properties.can_set_side_tone = can_set_side_tone;
-        state.device_properties.can_set_automatic_shutdown = can_set_automatic_shutdown;
-        state.device_properties.can_set_side_tone_volume = can_set_side_tone_volume;
-        state.device_properties.can_set_voice_prompt = can_set_voice_prompt;
-        state.device_properties.can_set_silent_mode = can_set_silent_mode;
-        state.device_properties.can_set_equalizer = can_set_equalizer;
-        state.device_properties.can_set_noise_gate = can_set_noise_gate;
+        let features = self.supported_features();
+
+        let properties = &mut self.get_device_state_mut().device_properties;
+        properties.capabilities = features.capabilities;
+        properties.side_tone_volume_range = features.side_tone_volume_range;
+        properties.automatic_shutdown_options = features.automatic_shutdown_options;
     }
 
     fn execute_headset_specific_functionality(&mut self) -> Result<(), DeviceError> {
@@ -958,59 +2060,161 @@ pub trait Device {
     }
 
     fn wait_for_updates(&mut self, duration: Duration) -> Option<Vec<DeviceEvent>> {
+        self.read_updates(duration).ok().flatten()
+    }
+
+    /// Like `wait_for_updates`, but tells a failed read apart from a timeout or an unknown report
+    fn read_updates(
+        &mut self,
+        duration: Duration,
+    ) -> Result<Option<Vec<DeviceEvent>>, DeviceError> {
         let mut buf = self.get_response_buffer();
         let res = self
             .get_device_state()
             .hid_device
-            .read_timeout(&mut buf[..], duration.as_millis() as i32)
-            .ok()?;
+            .as_ref()
+            .ok_or(DeviceError::NoDeviceFound())?
+            .read_timeout(&mut buf[..], duration.as_millis() as i32)?;
 
         if res == 0 {
-            return None;
+            return Ok(None);
+        }
+        hid_log::record("read", &buf[..res]);
+        if self.is_keepalive(&buf[..res]) {
+            debug_println!("Keepalive packet");
+            return Ok(None);
         }
 
-        self.get_event_from_device_response(&buf)
+        Ok(self.get_event_from_device_response(&buf))
+    }
+
+    /// Whether the response is a keepalive without any information.
+    /// Some devices periodically send all-zero reports.
+    fn is_keepalive(&self, response: &[u8]) -> bool {
+        response.iter().all(|byte| *byte == 0)
     }
 
     fn get_query_packets(&self) -> Vec<Vec<u8>> {
-        vec![
-            self.get_wireless_connected_status_packet(),
-            self.get_charging_packet(),
-            self.get_battery_packet(),
-            self.get_automatic_shut_down_packet(),
-            self.get_mute_packet(),
-            self.get_surround_sound_packet(),
-            self.get_mic_connected_packet(),
-            self.get_pairing_info_packet(),
-            self.get_product_color_packet(),
-            self.get_side_tone_packet(),
-            self.get_side_tone_volume_packet(),
-            self.get_voice_prompt_packet(),
-            self.get_sirk_packet(),
-            self.get_silent_mode_packet(),
-            self.get_noise_gate_packet(),
+        self.get_named_query_packets()
+            .into_iter()
+            .map(|(_, packet)| packet)
+            .collect()
+    }
+
+    /// The query packets together with the name of the property they query
+    fn get_named_query_packets(&self) -> Vec<(&'static str, Vec<u8>)> {
+        [
+            ("connected", self.get_wireless_connected_status_packet()),
+            ("charging_status", self.get_charging_packet()),
+            ("battery_level", self.get_battery_packet()),
+            (
+                "automatic_shutdown_interval",
+                self.get_automatic_shut_down_packet(),
+            ),
+            ("mic_muted", self.get_mute_packet()),
+            ("surround_sound_enabled", self.get_surround_sound_packet()),
+            ("mic_connected", self.get_mic_connected_packet()),
+            ("pairing_info", self.get_pairing_info_packet()),
+            ("product_color", self.get_product_color_packet()),
+            ("side_tone_enabled", self.get_side_tone_packet()),
+            ("side_tone_volume", self.get_side_tone_volume_packet()),
+            ("voice_prompt_enabled", self.get_voice_prompt_packet()),
+            ("sirk", self.get_sirk_packet()),
+            ("playback_muted", self.get_silent_mode_packet()),
+            ("noise_gate_enabled", self.get_noise_gate_packet()),
+            ("led", self.get_led_packet()),
+            ("active_eq_slot", self.get_active_eq_slot_packet()),
+            ("link_quality", self.get_link_quality_packet()),
         ]
         .into_iter()
-        .flatten()
+        .filter_map(|(name, packet)| packet.map(|packet| (name, packet)))
         .collect()
     }
 
+    /// Whether all queries can be written at once and the answers read afterwards.
+    /// Only devices whose answers name the query they belong to should opt in.
+    /// It is only used if `DeviceState::batched_refresh` is set.
+    fn supports_batched_refresh(&self) -> bool {
+        false
+    }
+
+    /// Writes all query packets before reading the answers, which takes well under a second
+    /// instead of one round trip per query. Returns the queries whose answer did not arrive.
+    fn batched_refresh(
+        &mut self,
+        packets: &[(&'static str, Vec<u8>)],
+    ) -> Result<Vec<&'static str>, DeviceError> {
+        for (_, packet) in packets {
+            debug_println!("Write packet: {packet:?}");
+            self.write_packet(packet)?;
+        }
+        let mut answered = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while answered.len() < packets.len() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            // a failed read would return immediately, so retrying until the deadline only spins
+            let Some(events) = self.read_updates(remaining)? else {
+                continue;
+            };
+            for event in events {
+                if let Some(name) = event.query_name() {
+                    if !answered.contains(&name) {
+                        answered.push(name);
+                    }
+                }
+                self.get_device_state_mut().update_self_with_event(&event);
+            }
+        }
+        Ok(packets
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !answered.contains(name))
+            .collect())
+    }
+
     /// Refreshes the state by querying all available information
     fn active_refresh_state(&mut self) -> Result<(), DeviceError> {
-        let packets = self.get_query_packets();
+        let mut packets = self.get_named_query_packets();
         self.execute_headset_specific_functionality()?;
 
+        let retries = self.get_device_state().query_retries;
         let mut responded = false;
-        for packet in packets.into_iter() {
-            self.prepare_write();
-            debug_println!("Write packet: {packet:?}");
-            self.get_device_state().write_hid_report(&packet)?;
-            std::thread::sleep(RESPONSE_DELAY);
-            if let Some(events) = self.wait_for_updates(Duration::from_secs(1)) {
-                for event in events {
-                    self.get_device_state_mut().update_self_with_event(&event);
+        if self.get_device_state().batched_refresh && self.supports_batched_refresh() {
+            let unanswered = self.batched_refresh(&packets)?;
+            responded = unanswered.len() < packets.len();
+            // the unanswered queries are repeated one by one below
+            packets.retain(|(name, _)| unanswered.contains(name));
+        }
+        let mut unanswered_queries = Vec::new();
+        for (name, packet) in packets.into_iter() {
+            let mut answered = false;
+            for attempt in 0..=retries {
+                if attempt > 0 {
+                    // back off exponentially in case the wireless link is flaky
+                    let backoff = RESPONSE_DELAY
+                        .saturating_mul(2u32.saturating_pow(attempt as u32))
+                        .min(MAX_RETRY_BACKOFF);
+                    debug_println!("No response for {name}, retrying in {backoff:?}");
+                    std::thread::sleep(backoff);
                 }
+                debug_println!("Write packet: {packet:?}");
+                self.write_packet(&packet)?;
+                std::thread::sleep(RESPONSE_DELAY);
+                if let Some(events) = self.wait_for_updates(Duration::from_secs(1)) {
+                    for event in events {
+                        self.get_device_state_mut().update_self_with_event(&event);
+                    }
+                    answered = true;
+                    break;
+                }
+            }
+            if answered {
                 responded = true;
+            } else {
+                unanswered_queries.push(name);
             }
             if !matches!(
                 self.get_device_state().device_properties.connected,
@@ -1020,11 +2224,91 @@ pub trait Device {
             }
         }
 
+        if !unanswered_queries.is_empty() {
+            debug_println!("Unanswered queries: {unanswered_queries:?}");
+        }
+        self.get_device_state_mut().unanswered_queries = unanswered_queries;
         if responded {
             Ok(())
         } else {
-            Err(DeviceError::NoResponse())
+            // a dongle that is still attached does not answer while the headset is off,
+            // otherwise the dongle itself stopped responding and has to be reconnected
+            let properties = &self.get_device_state().device_properties;
+            let dongle_present = list_compatible_devices().is_ok_and(|devices| {
+                devices.iter().any(|device| {
+                    device.vendor_id == properties.vendor_id
+                        && device.product_id == properties.product_id
+                        && (properties.serial_number.is_none()
+                            || device.serial_number == properties.serial_number)
+                })
+            });
+            if dongle_present {
+                self.get_device_state_mut().device_properties.connected = Some(false);
+                Err(DeviceError::HeadSetOff())
+            } else {
+                Err(DeviceError::NoResponse())
+            }
+        }
+    }
+
+    /// Only queries the battery level, skipping the headset specific functionality
+    /// that runs before an active refresh
+    fn quick_battery(&mut self) -> Result<Option<u8>, DeviceError> {
+        let packet = self
+            .get_battery_packet()
+            .ok_or(DeviceError::NotSupported())?;
+        debug_println!("Write packet: {packet:?}");
+        self.write_packet(&packet)?;
+        std::thread::sleep(RESPONSE_DELAY);
+        let events = self
+            .wait_for_updates(Duration::from_secs(1))
+            .ok_or(DeviceError::NoResponse())?;
+        for event in events {
+            self.get_device_state_mut().update_self_with_event(&event);
+        }
+        Ok(self.get_device_state().device_properties.battery_level)
+    }
+
+    /// Sends a single status query and returns whether the headset answered as connected.
+    fn is_powered_on(&mut self) -> bool {
+        let Some(packet) = self
+            .get_wireless_connected_status_packet()
+            .or_else(|| self.get_battery_packet())
+        else {
+            return false;
+        };
+        debug_println!("Write packet: {packet:?}");
+        if self.write_packet(&packet).is_err() {
+            return false;
+        }
+        std::thread::sleep(RESPONSE_DELAY);
+        self.wait_for_updates(Duration::from_secs(1))
+            .is_some_and(|events| {
+                events.iter().any(|event| match event {
+                    DeviceEvent::WirelessConnected(connected) => *connected,
+                    DeviceEvent::BatterLevel(_) => true,
+                    _ => false,
+                })
+            })
+    }
+
+    /// Sends every query once without retrying and returns the events decoded from each answer.
+    /// None means the query was not answered or the answer could not be decoded.
+    fn self_test(&mut self) -> Result<SelfTestReport, DeviceError> {
+        let packets = self.get_named_query_packets();
+        self.execute_headset_specific_functionality()?;
+        let mut results = Vec::new();
+        for (name, packet) in packets {
+            debug_println!("Write packet: {packet:?}");
+            self.write_packet(&packet)?;
+            std::thread::sleep(RESPONSE_DELAY);
+            let events = self.wait_for_updates(Duration::from_secs(1));
+            for event in events.iter().flatten() {
+                self.get_device_state_mut().update_self_with_event(event);
+            }
+            results.push((name, events));
         }
+        Ok(results)
     }
 
     /// Refreshes the state by listening for events
@@ -1044,8 +2328,7 @@ pub trait Device {
             }
         }
         if let Some(batter_packet) = self.get_battery_packet() {
-            self.prepare_write();
-            self.get_device_state().write_hid_report(&batter_packet)?;
+            self.write_packet(&batter_packet)?;
             std::thread::sleep(RESPONSE_DELAY);
             if let Some(events) = self.wait_for_updates(Duration::from_secs(1)) {
                 for event in events {
@@ -1068,9 +2351,21 @@ pub trait Device {
     fn try_apply(&mut self, command: DeviceEvent) -> Result<(), String> {
         match command {
             DeviceEvent::AutomaticShutdownAfter(delay) => {
+                if let Some(options) = self.automatic_shutdown_options() {
+                    let minutes = delay.as_secs() / 60;
+                    if !options.iter().any(|option| *option as u64 == minutes) {
+                        Err(format!(
+                            "ERROR: Automatic shutdown must be one of {} minutes on this device",
+                            options
+                                .iter()
+                                .map(|option| option.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ))?;
+                    }
+                }
                 if let Some(packet) = self.set_automatic_shut_down_packet(delay) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!(
                             "Failed to set automatic shutdown with error: {:?}",
                             err
@@ -1082,8 +2377,7 @@ pub trait Device {
             }
             DeviceEvent::Muted(mute) => {
                 if let Some(packet) = self.set_mute_packet(mute) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!("Failed to mute with error: {:?}", err))?;
                     }
                 } else {
@@ -1092,8 +2386,7 @@ pub trait Device {
             }
             DeviceEvent::SideToneOn(enable) => {
                 if let Some(packet) = self.set_side_tone_packet(enable) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!("Failed to enable side tone with error: {:?}", err))?;
                     }
                 } else {
@@ -1101,9 +2394,17 @@ pub trait Device {
                 }
             }
             DeviceEvent::SideToneVolume(volume) => {
+                if let Some(range) = self.side_tone_volume_range() {
+                    if !range.contains(&native_side_tone_volume(volume, &range)) {
+                        Err(format!(
+                            "ERROR: Side tone volume must be between {} and {} on this device",
+                            range.start(),
+                            range.end()
+                        ))?;
+                    }
+                }
                 if let Some(packet) = self.set_side_tone_volume_packet(volume) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!(
                             "Failed to set side tone volume with error: {:?}",
                             err
@@ -1118,8 +2419,7 @@ pub trait Device {
             }
             DeviceEvent::VoicePrompt(enable) => {
                 if let Some(packet) = self.set_voice_prompt_packet(enable) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!(
                             "Failed to enable voice prompt with error: {:?}",
                             err
@@ -1131,8 +2431,7 @@ pub trait Device {
             }
             DeviceEvent::SurroundSound(surround_sound) => {
                 if let Some(packet) = self.set_surround_sound_packet(surround_sound) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!(
                             "Failed to set surround sound with error: {:?}",
                             err
@@ -1144,8 +2443,7 @@ pub trait Device {
             }
             DeviceEvent::Silent(mute_playback) => {
                 if let Some(packet) = self.set_silent_mode_packet(mute_playback) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!("Failed to mute playback with error: {:?}", err))?;
                     }
                 } else {
@@ -1154,8 +2452,7 @@ pub trait Device {
             }
             DeviceEvent::NoiseGateActive(activate) => {
                 if let Some(packet) = self.set_noise_gate_packet(activate) {
-                    self.prepare_write();
-                    if let Err(err) = self.get_device_state().write_hid_report(&packet) {
+                    if let Err(err) = self.write_packet(&packet) {
                         Err(format!(
                             "Failed to activate noise gate with error: {:?}",
                             err
@@ -1165,6 +2462,38 @@ pub trait Device {
                     Err("ERROR: Activating noise gate is not supported on this device")?;
                 }
             }
+            DeviceEvent::LedState(color, brightness) => {
+                if let Some(packet) = self.set_led_packet(color, brightness.min(100)) {
+                    if let Err(err) = self.write_packet(&packet) {
+                        Err(format!("Failed to set the LED with error: {:?}", err))?;
+                    }
+                } else {
+                    Err("ERROR: LED control is not supported on this device")?;
+                }
+            }
+            DeviceEvent::LedBrightness(brightness) => {
+                let Some(color) = self.get_device_state().device_properties.led_color else {
+                    Err("ERROR: The LED color is not known yet")?
+                };
+                return self.try_apply(DeviceEvent::LedState(color, brightness));
+            }
+            DeviceEvent::ActiveEqSlot(slot) => {
+                if let Some(packet) = self.set_active_eq_slot_packet(slot) {
+                    if let Err(err) = self.write_packet(&packet) {
+                        Err(format!(
+                            "Failed to select the equalizer slot with error: {:?}",
+                            err
+                        ))?;
+                    }
+                } else {
+                    Err("ERROR: Equalizer slots are not supported on this device")?;
+                }
+            }
+            DeviceEvent::Equalizer(bands) => {
+                if let Err(err) = self.apply_eq_preset(&bands) {
+                    Err(format!("Failed to set the equalizer with error: {err}"))?;
+                }
+            }
             _ => (),
         }
         Ok(())
@@ -1178,7 +2507,331 @@ pub trait Device {
             .device_properties
             .device_name
             .clone();
-        self.get_device_state_mut().device_properties =
-            DeviceProperties::new(product_id, vendor_id, device_name)
+        let serial_number = self
+            .get_device_state()
+            .device_properties
+            .serial_number
+            .clone();
+        let dongle_usb_release = self
+            .get_device_state()
+            .device_properties
+            .dongle_usb_release
+            .clone();
+        // set once during initialization, so they are kept
+        let capabilities = self.capabilities();
+        let side_tone_volume_range = self
+            .get_device_state()
+            .device_properties
+            .side_tone_volume_range
+            .clone();
+        let automatic_shutdown_options = self
+            .get_device_state()
+            .device_properties
+            .automatic_shutdown_options;
+        let device_properties = &mut self.get_device_state_mut().device_properties;
+        *device_properties = DeviceProperties::new(product_id, vendor_id, device_name);
+        device_properties.serial_number = serial_number;
+        device_properties.dongle_usb_release = dongle_usb_release;
+        device_properties.capabilities = capabilities;
+        device_properties.side_tone_volume_range = side_tone_volume_range;
+        device_properties.automatic_shutdown_options = automatic_shutdown_options;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_rate_is_a_number_in_json() {
+        let mut properties = DeviceProperties::new(0x05B7, 0x03F0, None);
+        properties.battery_rate = Some(1.5);
+        assert!(properties
+            .to_json_fields()
+            .contains(&"\"battery_rate\": 1.5".to_string()));
+        assert!(properties
+            .to_string_with_padding(0)
+            .contains("Battery rate: +1.5%/h"));
+        properties.battery_rate = Some(-2.25);
+        assert!(properties
+            .to_json_fields()
+            .contains(&"\"battery_rate\": -2.2".to_string()));
+    }
+
+    #[test]
+    fn time_to_full_is_minutes_in_json() {
+        let mut properties = DeviceProperties::new(0x05B7, 0x03F0, None);
+        properties.charging = Some(ChargingStatus::Charging);
+        properties.battery_level = Some(40);
+        properties.battery_rate = Some(60.0);
+        assert!(properties
+            .to_json_fields()
+            .contains(&"\"time_to_full\": 60".to_string()));
+        assert!(properties
+            .to_string_with_padding(0)
+            .contains("Time to full: ~60min"));
+    }
+
+    #[test]
+    fn link_quality_is_shown_as_bars() {
+        let mut properties = DeviceProperties::new(0x05B7, 0x03F0, None);
+        assert_eq!(properties.link_quality_bars(), None);
+        for (quality, bars) in [
+            (0, 0),
+            (16, 0),
+            (17, 1),
+            (49, 1),
+            (50, 2),
+            (84, 3),
+            (255, 3),
+        ] {
+            properties.link_quality = Some(quality);
+            assert_eq!(properties.link_quality_bars(), Some(bars), "{quality}");
+        }
+        properties.link_quality = Some(60);
+        assert_eq!(
+            properties.to_string_with_padding(0),
+            "Link quality: 60% ▂▄▁"
+        );
+    }
+
+    #[test]
+    fn dongle_id_falls_back_to_the_hid_path() {
+        let mut first = DeviceState::from_parts(None, 0x03F0, 0x018B);
+        let mut second = DeviceState::from_parts(None, 0x03F0, 0x018B);
+        first.hid_path = Some("/dev/hidraw1".to_string());
+        second.hid_path = Some("/dev/hidraw4".to_string());
+        assert_eq!(first.dongle_id(), Some("/dev/hidraw1"));
+        assert_ne!(first.dongle_id(), second.dongle_id());
+
+        first.device_properties.serial_number = Some("ABC".to_string());
+        second.device_properties.serial_number = Some("ABC".to_string());
+        assert_eq!(first.dongle_id(), Some("ABC"));
+        assert_eq!(first.dongle_id(), second.dongle_id());
+    }
+
+    #[test]
+    fn to_json_shape_of_every_event() {
+        let mut bands = [0.0; NUM_BANDS];
+        bands[0] = 1.5;
+        bands[1] = -2.0;
+        let cases = [
+            (DeviceEvent::BatterLevel(80), "battery_level", "80"),
+            (DeviceEvent::Muted(true), "muted", "true"),
+            (DeviceEvent::MicConnected(false), "mic_connected", "false"),
+            (
+                DeviceEvent::Charging(ChargingStatus::NotCharging),
+                "charging",
+                "\"not_charging\"",
+            ),
+            (
+                DeviceEvent::Charging(ChargingStatus::Charging),
+                "charging",
+                "\"charging\"",
+            ),
+            (
+                DeviceEvent::Charging(ChargingStatus::FullyCharged),
+                "charging",
+                "\"fully_charged\"",
+            ),
+            (
+                DeviceEvent::Charging(ChargingStatus::Unknown(9)),
+                "charging",
+                "\"unknown_9\"",
+            ),
+            (
+                DeviceEvent::AutomaticShutdownAfter(Duration::from_secs(600)),
+                "automatic_shutdown_after",
+                "600",
+            ),
+            (DeviceEvent::PairingInfo(2), "pairing_info", "2"),
+            (
+                DeviceEvent::ProductColor(Color::BlackRed),
+                "product_color",
+                "\"black_red\"",
+            ),
+            (DeviceEvent::SideToneOn(true), "side_tone_on", "true"),
+            (DeviceEvent::SideToneVolume(251), "side_tone_volume", "251"),
+            (DeviceEvent::VoicePrompt(false), "voice_prompt", "false"),
+            (
+                DeviceEvent::WirelessConnected(true),
+                "wireless_connected",
+                "true",
+            ),
+            (DeviceEvent::SurroundSound(true), "surround_sound", "true"),
+            (DeviceEvent::Silent(false), "silent", "false"),
+            (
+                DeviceEvent::RequireSIRKReset(true),
+                "require_sirk_reset",
+                "true",
+            ),
+            (
+                DeviceEvent::NoiseGateActive(false),
+                "noise_gate_active",
+                "false",
+            ),
+            (
+                DeviceEvent::ButtonPress(Button::VolumeUp),
+                "button_press",
+                "\"volume_up\"",
+            ),
+            (
+                DeviceEvent::ButtonPress(Button::VolumeDown),
+                "button_press",
+                "\"volume_down\"",
+            ),
+            (
+                DeviceEvent::ButtonPress(Button::PlayPause),
+                "button_press",
+                "\"play_pause\"",
+            ),
+            (
+                DeviceEvent::LedState(Color::Rgb(0, 128, 255), 25),
+                "led_state",
+                "{\"color\": \"#0080ff\", \"brightness\": 25}",
+            ),
+            (DeviceEvent::LedBrightness(75), "led_brightness", "75"),
+            (
+                DeviceEvent::Equalizer(bands),
+                "equalizer",
+                "[1.5, -2, 0, 0, 0, 0, 0, 0, 0, 0]",
+            ),
+            (DeviceEvent::ActiveEqSlot(3), "active_eq_slot", "3"),
+            (DeviceEvent::LinkQuality(90), "link_quality", "90"),
+        ];
+        for (event, event_type, value) in cases {
+            assert_eq!(
+                event.to_json(),
+                format!("{{\"type\": \"{event_type}\", \"value\": {value}}}"),
+                "{event:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn color_from_u8_maps_known_values() {
+        assert_eq!(Color::from(0), Color::BlackBlack);
+        assert_eq!(Color::from(1), Color::WhiteWhite);
+        assert_eq!(Color::from(2), Color::BlackRed);
+        assert_eq!(Color::from(7), Color::UnknownColor(7));
+        assert_eq!(Color::from([255, 0, 16]), Color::Rgb(255, 0, 16));
+    }
+
+    #[test]
+    fn color_display_and_led_state_json() {
+        assert_eq!(Color::BlackRed.to_string(), "Red");
+        assert_eq!(Color::UnknownColor(7).to_string(), "Unknown color 7");
+        assert_eq!(Color::Rgb(255, 0, 16).to_string(), "#FF0010");
+        assert_eq!(
+            DeviceEvent::LedState(Color::Rgb(255, 0, 16), 50).to_json(),
+            "{\"type\": \"led_state\", \"value\": {\"color\": \"#ff0010\", \"brightness\": 50}}"
+        );
+        assert_eq!(
+            DeviceEvent::LedState(Color::WhiteWhite, 100).to_json(),
+            "{\"type\": \"led_state\", \"value\": {\"color\": \"white_white\", \"brightness\": 100}}"
+        );
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_and_control_characters() {
+        assert_eq!(json_string("Cloud III"), "\"Cloud III\"");
+        assert_eq!(json_string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(json_string("C:\\path"), "\"C:\\\\path\"");
+        assert_eq!(json_string("a\nb\u{1}"), "\"a\\nb\\u0001\"");
+    }
+
+    #[test]
+    fn signed_side_tone_volume_keeps_negative_values_in_range() {
+        assert_eq!(clamp_signed_side_tone_volume(251, 5), 251);
+        assert_eq!(clamp_signed_side_tone_volume(5, 5), 5);
+        assert_eq!(clamp_signed_side_tone_volume(6, 5), 0);
+        assert_eq!(clamp_signed_side_tone_volume(250, 5), 0);
+        assert_eq!(native_side_tone_volume(251, &(-5..=5)), -5);
+        assert_eq!(native_side_tone_volume(251, &(0..=255)), 251);
+    }
+
+    #[test]
+    fn side_tone_volume_percent_round_trips_at_both_ends() {
+        let range = -5..=5;
+        assert_eq!(side_tone_volume_from_percent(0, &range), 251);
+        assert_eq!(side_tone_volume_from_percent(50, &range), 0);
+        assert_eq!(side_tone_volume_from_percent(100, &range), 5);
+
+        let mut properties = DeviceProperties::new(0x05B7, 0x03F0, None);
+        properties.side_tone_volume_range = Some(range);
+        for (raw, percent) in [(251, 0), (0, 50), (5, 100)] {
+            properties.side_tone_volume = Some(raw);
+            assert_eq!(properties.side_tone_volume_percent(), Some(percent));
+        }
+    }
+
+    #[test]
+    fn side_tone_volume_options_follow_the_device_range() {
+        let mut properties = DeviceProperties::new(0x05B7, 0x03F0, None);
+        properties.side_tone_volume_range = Some(-5..=5);
+        let options = properties
+            .get_properties()
+            .into_iter()
+            .find_map(|property| match property {
+                PropertyDescriptorWrapper::Int(property, options)
+                    if property.name == "side_tone_volume" =>
+                {
+                    Some(options)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(options.values, [251, 252, 253, 254, 255, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(options.format(251, ""), "-5");
+        assert_eq!(options.format(5, ""), "5");
+
+        let options = IntOptions::from_range(&(0..=100));
+        assert_eq!(options.values, [0, 10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
+        assert!(!options.signed);
+    }
+
+    #[test]
+    fn to_string_with_padding_lists_only_known_values() {
+        let mut properties = DeviceProperties::new(0x018B, 0x03F0, None);
+        assert_eq!(properties.to_string_with_padding(10), "");
+
+        properties.battery_level = Some(80);
+        properties.muted = Some(true);
+        properties.automatic_shutdown_after = Some(Duration::from_secs(30 * 60));
+        assert_eq!(
+            properties.to_string_with_padding(10),
+            "Battery level: 80%\n\
+             Muted:     true\n\
+             Automatic shutdown after: 30min"
+        );
+
+        properties.automatic_shutdown_after = Some(Duration::ZERO);
+        assert!(properties
+            .to_string_with_padding(0)
+            .ends_with("Automatic shutdown after: never"));
+    }
+
+    #[test]
+    fn to_string_with_readonly_info_marks_settings_that_cannot_be_changed() {
+        let mut properties = DeviceProperties::new(0x018B, 0x03F0, None);
+        properties.battery_level = Some(80);
+        properties.muted = Some(false);
+        properties.side_tone_on = Some(true);
+        properties.capabilities.can_set_side_tone = true;
+        assert_eq!(
+            properties.to_string_with_readonly_info(10),
+            "Battery level: 80%\n\
+             Muted:     false (read-only)\n\
+             Side tone: true"
+        );
+
+        properties.capabilities.can_set_mute = true;
+        properties.capabilities.can_set_side_tone = false;
+        assert_eq!(
+            properties.to_string_with_readonly_info(10),
+            "Battery level: 80%\n\
+             Muted:     false\n\
+             Side tone: true (read-only)"
+        );
     }
 }
@@ -0,0 +1,48 @@
+use std::{fs, path::PathBuf};
+
+use hyper_headset::devices::DeviceProperties;
+
+use crate::tray_battery_icon_state::TrayBatteryIconState;
+
+/// Writes `{"percent": 47, "charging": false, "icon": "battery-040"}` to a file
+/// whenever the battery state changes, so EWW widgets can read it with `defpoll` or `deflisten`.
+/// `percent` is null if no connected headset was found.
+pub struct EwwFile {
+    path: PathBuf,
+    monochrome_icons: bool,
+    last_state: Option<TrayBatteryIconState>,
+}
+
+impl EwwFile {
+    pub fn new(path: PathBuf, monochrome_icons: bool) -> Self {
+        EwwFile {
+            path,
+            monochrome_icons,
+            last_state: None,
+        }
+    }
+
+    pub fn update(&mut self, properties: Option<&DeviceProperties>) {
+        let state = TrayBatteryIconState::from_device_properties(properties);
+        if self.last_state == Some(state) {
+            return;
+        }
+        self.last_state = Some(state);
+        let (percent, charging) = match state {
+            TrayBatteryIconState::Connected { percent, charging } => {
+                (percent.to_string(), charging)
+            }
+            _ => ("null".to_string(), false),
+        };
+        let icon = state.linux_icon_name(self.monochrome_icons, None);
+        let content =
+            format!("{{\"percent\": {percent}, \"charging\": {charging}, \"icon\": \"{icon}\"}}\n");
+        // write to a temporary file first so readers never see a partial file
+        let tmp_path = self.path.with_extension("tmp");
+        if let Err(e) =
+            fs::write(&tmp_path, content).and_then(|_| fs::rename(&tmp_path, &self.path))
+        {
+            eprintln!("Failed to write {}: {e}", self.path.display());
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::config_dir, devices::DeviceProperties};
+
+const LAST_STATE_FILE: &str = "last_state.json";
+/// Minimum time between two writes of an unchanged state
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The last known state of a headset, persisted across restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastState {
+    pub device_name: Option<String>,
+    pub battery_level: u8,
+    pub seen_at: SystemTime,
+}
+
+impl LastState {
+    /// Returns None if the properties contain nothing worth remembering.
+    pub fn from_device_properties(properties: &DeviceProperties) -> Option<Self> {
+        if !properties.connected.unwrap_or(false) {
+            return None;
+        }
+        Some(LastState {
+            device_name: properties.device_name.clone(),
+            battery_level: properties.battery_level?,
+            seen_at: SystemTime::now(),
+        })
+    }
+
+    /// Updates `last_state` with the properties and persists it
+    /// if it changed or the stored one is older than a minute.
+    pub fn remember(last_state: &mut Option<Self>, properties: &DeviceProperties) {
+        let Some(new_state) = Self::from_device_properties(properties) else {
+            return;
+        };
+        let outdated = last_state.as_ref().is_none_or(|old| {
+            old.battery_level != new_state.battery_level
+                || old.device_name != new_state.device_name
+                || old.seen_at.elapsed().unwrap_or_default() >= SAVE_INTERVAL
+        });
+        if outdated {
+            if let Err(e) = new_state.save() {
+                eprintln!("Failed to save the last state: {e}");
+            }
+            *last_state = Some(new_state);
+        }
+    }
+
+    pub fn load() -> Option<Self> {
+        let content = fs::read_to_string(config_dir()?.join(LAST_STATE_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(dir) = config_dir() else {
+            return Ok(());
+        };
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(LAST_STATE_FILE), serde_json::to_string(self)?)
+    }
+
+    /// e.g. "Last seen: 47% (5 min ago)"
+    pub fn describe(&self) -> String {
+        let minutes = self.seen_at.elapsed().unwrap_or_default().as_secs() / 60;
+        let ago = match minutes {
+            0 => "just now".to_string(),
+            1..60 => format!("{minutes} min ago"),
+            60..1440 => format!("{} h ago", minutes / 60),
+            _ => format!("{} d ago", minutes / 1440),
+        };
+        format!("Last seen: {}% ({ago})", self.battery_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json() {
+        let state = LastState {
+            device_name: Some("HyperX Cloud III".to_string()),
+            battery_level: 47,
+            seen_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<LastState>(&json).unwrap(), state);
+    }
+}
@@ -6,7 +6,9 @@ use std::{fs, io, process::Command, time::Duration};
 use dialog::{Choice, DialogBox};
 
 // #![warn(missing_docs)]
+pub mod config;
 pub mod devices;
+pub mod last_state;
 
 #[cfg(target_os = "linux")]
 pub mod bluetooth;
@@ -15,16 +17,30 @@ pub mod bluetooth;
 mod airoha_race;
 
 pub static VERBOSE: OnceLock<bool> = OnceLock::new();
+/// Set by `--quiet` to hide informational messages, errors are still printed.
+pub static QUIET: OnceLock<bool> = OnceLock::new();
 
+/// Prints informational messages like "Connecting to ..." to stderr unless `--quiet` is given.
+#[macro_export]
+macro_rules! info_println {
+    ($($args:tt)*) => {
+        if !*$crate::QUIET.get().unwrap_or(&false) {
+            eprintln!($($args)*);
+        }
+    };
+}
+
+/// Prints protocol tracing in debug builds or with `--verbose`.
+/// It goes to stderr so that machine-readable output on stdout stays intact.
 #[macro_export]
 macro_rules! debug_println {
     ($($args:tt)*) => {
         #[cfg(debug_assertions)]
-        println!($($args)*);
+        eprintln!($($args)*);
 
         #[cfg(not(debug_assertions))]
         if *$crate::VERBOSE.get().unwrap_or(&false) {
-            println!($($args)*);
+            eprintln!($($args)*);
         }
     };
 }
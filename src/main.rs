@@ -1,5 +1,17 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
+#[cfg(target_os = "linux")]
+mod control_socket;
+
+#[cfg(target_os = "linux")]
+mod dbus_service;
+
+#[cfg(target_os = "linux")]
+mod eww_file;
+
+#[cfg(target_os = "linux")]
+mod notifications;
+
 #[cfg(target_os = "linux")]
 mod status_tray;
 
@@ -9,13 +21,28 @@ mod status_tray_not_linux;
 #[cfg(not(target_os = "macos"))]
 mod tray_battery_icon_state;
 
+/// The value given on the command line, otherwise the one from the config file,
+/// otherwise the default value of the argument.
+fn arg_or_config<T: Clone + Send + Sync + 'static>(
+    matches: &clap::ArgMatches,
+    id: &str,
+    config: Option<T>,
+) -> Option<T> {
+    if matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine) {
+        matches.get_one::<T>(id).cloned()
+    } else {
+        config.or_else(|| matches.get_one::<T>(id).cloned())
+    }
+}
+
 #[cfg(not(target_os = "linux"))]
 fn main() {
     use clap::ArgAction;
     use std::sync::mpsc;
 
+    use hyper_headset::config::Config;
     use hyper_headset::devices::{DeviceEvent, DeviceProperties};
-    use hyper_headset::VERBOSE;
+    use hyper_headset::{QUIET, VERBOSE};
     use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
 
     use crate::status_tray_not_linux::TrayApp;
@@ -26,6 +53,8 @@ fn main() {
     event_loop.set_control_flow(ControlFlow::Wait);
 
     let (tx, rx) = mpsc::channel::<DeviceEvent>();
+    let config = Config::load();
+    let profiles = config.profiles.clone();
 
     std::thread::spawn(move || {
         use std::time::Duration;
@@ -33,7 +62,9 @@ fn main() {
         use clap::{Arg, Command};
         use enigo::{Direction, Enigo, Key, Keyboard, Settings};
 
-        use hyper_headset::devices::connect_compatible_device;
+        use hyper_headset::config::LOW_BATTERY_THRESHOLD;
+        use hyper_headset::debug_println;
+        use hyper_headset::devices::{connect_compatible_device, Button, DeviceError};
 
         let matches = Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -48,6 +79,22 @@ fn main() {
                 .default_value("3")
                 .value_parser(clap::value_parser!(u64)),
         )
+        .arg(
+            Arg::new("active_refresh_every")
+                .long("active_refresh_every")
+                .required(false)
+                .help("Query all properties every n refreshes, otherwise only the battery is queried")
+                .default_value("30")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("low_battery_threshold")
+                .long("low_battery_threshold")
+                .required(false)
+                .help("Battery levels below this percentage are shown as low")
+                .default_value("30")
+                .value_parser(clap::value_parser!(u8).range(0..=100)),
+        )
         .arg(
             Arg::new("press_mute_key")
                 .long("press_mute_key")
@@ -63,24 +110,110 @@ fn main() {
             .required(false)
             .help("Use verbose output ")
         )
+        .arg(Arg::new("quiet")
+            .long("quiet")
+            .short('q')
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .conflicts_with("verbose")
+            .help("Only print errors")
+        )
+        .arg(Arg::new("charging_debounce")
+            .long("charging_debounce")
+            .required(false)
+            .help("Number of consecutive readings a new charging status must persist before it is shown")
+            .default_value("1")
+            .value_parser(clap::value_parser!(usize))
+        )
+        .arg(Arg::new("query_retries")
+            .long("query_retries")
+            .required(false)
+            .help("How often an unanswered query is repeated, at most 5 times, with increasing delays, when refreshing all properties. Retries slow down every refresh, so only use them on a flaky connection")
+            .default_value("0")
+            .value_parser(clap::value_parser!(u8).range(0..=5))
+        )
+        .arg(Arg::new("batched_refresh")
+            .long("batched_refresh")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Send all queries at once when refreshing all properties. This is faster but only works on headsets whose answers name their query")
+        )
+        .arg(Arg::new("battery_smoothing")
+            .long("battery_smoothing")
+            .required(false)
+            .help("Only show small changes of the battery level once they persist over several readings")
+            .default_value("false")
+            .value_parser(clap::value_parser!(bool))
+        )
+        .arg(Arg::new("retry_partial_refresh")
+            .long("retry_partial_refresh")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Immediately query the headset again if it did not answer all queries after connecting")
+        )
+        .arg(Arg::new("media_keys")
+            .long("media_keys")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Forward the volume and play/pause buttons of the headset as media keys")
+        )
         .get_matches();
 
         VERBOSE.set(matches.get_flag("verbose")).unwrap();
+        QUIET.set(matches.get_flag("quiet")).unwrap();
+        let retry_partial_refresh = matches.get_flag("retry_partial_refresh");
+        let charging_debounce = *matches.get_one::<usize>("charging_debounce").unwrap_or(&1);
+        let query_retries = *matches.get_one::<u8>("query_retries").unwrap_or(&0) as usize;
+        let batched_refresh = matches.get_flag("batched_refresh");
 
         let press_mute_key = *matches.get_one::<bool>("press_mute_key").unwrap_or(&true);
-        let mut enigo = if press_mute_key {
-            match Enigo::new(&Settings::default()) {
-                Ok(enigo) => Some(enigo),
-                Err(e) => {
-                    eprintln!("Virtual mute key failed to initialize: {e}");
-                    None
-                }
+        let media_keys = matches.get_flag("media_keys");
+        let new_enigo = || match Enigo::new(&Settings::default()) {
+            Ok(enigo) => Some(enigo),
+            Err(e) => {
+                eprintln!("Virtual keyboard failed to initialize: {e}");
+                None
             }
-        } else {
-            None
         };
-        let refresh_interval = *matches.get_one::<u64>("refresh_interval").unwrap_or(&3);
+        let mut enigo = if press_mute_key { new_enigo() } else { None };
+        let refresh_interval =
+            arg_or_config(&matches, "refresh_interval", config.refresh_interval_secs).unwrap_or(3);
+        let active_refresh_every = arg_or_config(
+            &matches,
+            "active_refresh_every",
+            config.active_refresh_every_n,
+        )
+        .unwrap_or(30)
+        .max(1);
+        let low_battery_threshold = arg_or_config(
+            &matches,
+            "low_battery_threshold",
+            config.low_battery_threshold,
+        )
+        .unwrap_or(30);
+        let _ = LOW_BATTERY_THRESHOLD.set(low_battery_threshold);
+        let battery_smoothing =
+            arg_or_config(&matches, "battery_smoothing", config.battery_smoothing).unwrap_or(false);
         let refresh_interval = Duration::from_secs(refresh_interval);
+        let (button_sender, button_receiver) = mpsc::channel::<Button>();
+        // buttons are forwarded on their own thread so they do not wait for the refresh interval
+        if media_keys {
+            std::thread::spawn(move || {
+                let mut enigo = new_enigo();
+                for button in button_receiver {
+                    let key = match button {
+                        Button::VolumeUp => Key::VolumeUp,
+                        Button::VolumeDown => Key::VolumeDown,
+                        Button::PlayPause => Key::MediaPlayPause,
+                    };
+                    if let Some(enigo) = &mut enigo {
+                        if let Err(e) = enigo.key(key, Direction::Click) {
+                            eprintln!("Failed to press media key: {e}");
+                        }
+                    }
+                }
+            });
+        }
 
         loop {
             let mut device = loop {
@@ -94,36 +227,52 @@ fn main() {
                 std::thread::sleep(Duration::from_secs(1));
             };
 
+            device.set_charging_debounce(charging_debounce);
+            device.set_battery_smoothing(battery_smoothing);
+            device.set_query_retries(query_retries);
+            device.set_batched_refresh(batched_refresh);
+            if media_keys {
+                device.set_button_sender(button_sender.clone());
+            }
+
             // Run loop
             let mut run_counter = 0;
             loop {
                 let mute_state = device.device_properties().muted;
-                match if run_counter % 30 == 0 {
+                match if run_counter == 0 && retry_partial_refresh {
+                    device.active_refresh_state_with_retry()
+                } else if run_counter % active_refresh_every == 0 {
                     device.active_refresh_state()
                 } else {
                     device.passive_refresh_state()
                 } {
                     Ok(()) => (),
+                    // the dongle is still present, only the headset is off
+                    Err(DeviceError::HeadSetOff()) => {
+                        debug_println!("Headset is off");
+                    }
                     Err(error) => {
                         eprintln!("{error}");
                         let _ = proxy.send_event(Some(device.device_properties()));
                         break; // try to reconnect
                     }
                 };
-                if mute_state.is_some() && mute_state != device.device_properties().muted {
+                if press_mute_key
+                    && mute_state.is_some()
+                    && mute_state != device.device_properties().muted
+                {
                     if let Some(enigo) = &mut enigo {
                         if let Err(e) = enigo.key(Key::F20, Direction::Click) {
                             eprintln!("Failed to press key on mute: {e}");
                         }
                     }
                 }
-
                 // with the default refresh_interval the state is only actively queried every 3min
                 // querying the device to frequently can lead to instability
                 let first = rx.recv_timeout(refresh_interval);
                 for command in first.into_iter().chain(rx.try_iter()) {
                     let _ = device.try_apply(command);
-                    std::thread::sleep(hyper_headset::devices::RESPONSE_DELAY);
+                    std::thread::sleep(device.settle_delay());
                     let _ = device.active_refresh_state();
                 }
 
@@ -133,7 +282,7 @@ fn main() {
         }
     });
 
-    event_loop.run_app(&mut TrayApp::new(tx)).unwrap();
+    event_loop.run_app(&mut TrayApp::new(tx, profiles)).unwrap();
 }
 
 #[cfg(target_os = "linux")]
@@ -141,14 +290,20 @@ fn main() {
     use clap::ArgAction;
     use clap::{Arg, Command};
     use enigo::{Direction, Enigo, Key, Keyboard, Settings};
-    use std::sync::mpsc;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
     use std::time::Duration;
 
-    use hyper_headset::devices::connect_compatible_device;
+    use eww_file::EwwFile;
+    use hyper_headset::config::{Config, LOW_BATTERY_THRESHOLD};
+    use hyper_headset::devices::{connect_compatible_device, DeviceError};
+    use hyper_headset::{debug_println, info_println};
+    use notifications::{NotificationKind, Notifier, DEFAULT_NOTIFICATIONS};
     use status_tray::{StatusTray, TrayHandler};
 
     use hyper_headset::prompt_user_for_udev_rule;
-    use hyper_headset::{act_as_askpass_handler, VERBOSE};
+    use hyper_headset::{act_as_askpass_handler, QUIET, VERBOSE};
 
     if let Ok(name) = std::env::current_exe() {
         if let Some(name) = name.to_str() {
@@ -173,6 +328,22 @@ fn main() {
                 .default_value("3")
                 .value_parser(clap::value_parser!(u64)),
         )
+        .arg(
+            Arg::new("active_refresh_every")
+                .long("active_refresh_every")
+                .required(false)
+                .help("Query all properties every n refreshes, otherwise only the battery is queried")
+                .default_value("30")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("low_battery_threshold")
+                .long("low_battery_threshold")
+                .required(false)
+                .help("Battery levels below this percentage are shown as low")
+                .default_value("30")
+                .value_parser(clap::value_parser!(u8).range(0..=100)),
+        )
         .arg(
             Arg::new("press_mute_key")
                 .long("press_mute_key")
@@ -188,12 +359,83 @@ fn main() {
             .required(false)
             .help("Use verbose output ")
         )
+        .arg(Arg::new("quiet")
+            .long("quiet")
+            .short('q')
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .conflicts_with("verbose")
+            .help("Only print errors")
+        )
+        .arg(Arg::new("charging_debounce")
+            .long("charging_debounce")
+            .required(false)
+            .help("Number of consecutive readings a new charging status must persist before it is shown")
+            .default_value("1")
+            .value_parser(clap::value_parser!(usize))
+        )
+        .arg(Arg::new("query_retries")
+            .long("query_retries")
+            .required(false)
+            .help("How often an unanswered query is repeated, at most 5 times, with increasing delays, when refreshing all properties. Retries slow down every refresh, so only use them on a flaky connection")
+            .default_value("0")
+            .value_parser(clap::value_parser!(u8).range(0..=5))
+        )
+        .arg(Arg::new("batched_refresh")
+            .long("batched_refresh")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Send all queries at once when refreshing all properties. This is faster but only works on headsets whose answers name their query")
+        )
+        .arg(Arg::new("battery_smoothing")
+            .long("battery_smoothing")
+            .required(false)
+            .help("Only show small changes of the battery level once they persist over several readings")
+            .default_value("false")
+            .value_parser(clap::value_parser!(bool))
+        )
+        .arg(Arg::new("retry_partial_refresh")
+            .long("retry_partial_refresh")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Immediately query the headset again if it did not answer all queries after connecting")
+        )
+        .arg(Arg::new("control_socket")
+            .long("control_socket")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Accept JSON commands on a Unix socket in the config directory, e.g. {\"get_state\": true}")
+        )
+        .arg(Arg::new("dbus")
+            .long("dbus")
+            .action(ArgAction::SetTrue)
+            .required(false)
+            .help("Offer org.hyperheadset.Control on the session bus to query and control the headset")
+        )
         .arg(Arg::new("monochrome_icons")
             .long("monochrome-icons")
             .action(ArgAction::SetTrue)
             .required(false)
             .help("Use the symbolic (monochrome) variants of the system tray icons")
         )
+        .arg(Arg::new("battery_pixmap")
+            .long("battery-pixmap")
+            .required(false)
+            .help("Show a rendered battery icon with a bolt while charging. \"auto\" picks the colors based on the icon theme")
+            .value_parser(["auto", "light", "dark"])
+        )
+        .arg(Arg::new("instance_name")
+            .long("instance-name")
+            .required(false)
+            .help("Name of this instance. Use this to tell multiple running instances apart")
+            .value_parser(clap::value_parser!(String))
+        )
+        .arg(Arg::new("eww_file")
+            .long("eww_file")
+            .required(false)
+            .help("Write the battery state as JSON for EWW widgets to this file, e.g. {\"percent\": 47, \"charging\": false, \"icon\": \"battery-040\"}")
+            .value_parser(clap::value_parser!(String))
+        )
         .get_matches();
 
     let press_mute_key = *matches.get_one::<bool>("press_mute_key").unwrap_or(&true);
@@ -209,37 +451,126 @@ fn main() {
         None
     };
     VERBOSE.set(matches.get_flag("verbose")).unwrap();
+    QUIET.set(matches.get_flag("quiet")).unwrap();
+    let retry_partial_refresh = matches.get_flag("retry_partial_refresh");
+    let charging_debounce = *matches.get_one::<usize>("charging_debounce").unwrap_or(&1);
+    let query_retries = *matches.get_one::<u8>("query_retries").unwrap_or(&0) as usize;
+    let batched_refresh = matches.get_flag("batched_refresh");
     let monochrome_icons = matches.get_flag("monochrome_icons");
+    let battery_pixmap = matches.get_one::<String>("battery_pixmap");
+    let instance_name = matches.get_one::<String>("instance_name").cloned();
 
-    let refresh_interval = *matches.get_one::<u64>("refresh_interval").unwrap_or(&3);
+    let config = Config::load();
+    let refresh_interval =
+        arg_or_config(&matches, "refresh_interval", config.refresh_interval_secs).unwrap_or(3);
+    let active_refresh_every = arg_or_config(
+        &matches,
+        "active_refresh_every",
+        config.active_refresh_every_n,
+    )
+    .unwrap_or(30)
+    .max(1);
+    let low_battery_threshold = arg_or_config(
+        &matches,
+        "low_battery_threshold",
+        config.low_battery_threshold,
+    )
+    .unwrap_or(30);
+    let _ = LOW_BATTERY_THRESHOLD.set(low_battery_threshold);
+    let battery_smoothing =
+        arg_or_config(&matches, "battery_smoothing", config.battery_smoothing).unwrap_or(false);
+    let notify_on = match &config.notify_on {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| {
+                let kind = NotificationKind::from_name(name);
+                if kind.is_none() {
+                    eprintln!("Unknown notification: {name}");
+                }
+                kind
+            })
+            .collect(),
+        None => DEFAULT_NOTIFICATIONS.to_vec(),
+    };
+    let mut notifier = Notifier::new(notify_on);
+    let mut eww_file = arg_or_config(&matches, "eww_file", config.eww_file.clone())
+        .map(|path| EwwFile::new(PathBuf::from(path), monochrome_icons));
     let refresh_interval = Duration::from_secs(refresh_interval);
     let (tx, rx) = mpsc::channel();
-    let tray_handler = TrayHandler::new(StatusTray::new(tx, monochrome_icons));
+    // set to drop the current device and open it again
+    let reconnect = Arc::new(AtomicBool::new(false));
+    let shared_properties = control_socket::SharedProperties::default();
+    let button_subscribers = control_socket::ButtonSubscribers::default();
+    if matches.get_flag("control_socket") {
+        control_socket::spawn(
+            shared_properties.clone(),
+            tx.clone(),
+            reconnect.clone(),
+            button_subscribers.clone(),
+            config.profiles.clone(),
+        );
+    }
+    if matches.get_flag("dbus") {
+        dbus_service::spawn(
+            shared_properties.clone(),
+            tx.clone(),
+            config.profiles.clone(),
+        );
+    }
+    let (button_sender, button_receiver) = mpsc::channel();
+    let mut tray_handler = TrayHandler::new(StatusTray::new(
+        tx,
+        reconnect.clone(),
+        monochrome_icons,
+        battery_pixmap.map(|s| s.as_str()),
+        instance_name,
+        config.profiles.clone(),
+    ));
     loop {
         let mut device = loop {
             match connect_compatible_device() {
                 Ok(d) => break d,
                 Err(e) => {
                     tray_handler.clear_state();
+                    if let Some(eww_file) = &mut eww_file {
+                        eww_file.update(None);
+                    }
+                    *shared_properties.lock().unwrap() = None;
                     eprintln!("Connecting failed with error: {e}");
                 }
             }
             std::thread::sleep(Duration::from_secs(1));
         };
 
+        device.set_charging_debounce(charging_debounce);
+        device.set_battery_smoothing(battery_smoothing);
+        device.set_query_retries(query_retries);
+        device.set_batched_refresh(batched_refresh);
+        device.set_button_sender(button_sender.clone());
+
         // Run loop
         let mut run_counter = 0;
         loop {
             let mute_state = device.device_properties().muted;
-            match if run_counter % 30 == 0 {
+            match if run_counter == 0 && retry_partial_refresh {
+                device.active_refresh_state_with_retry()
+            } else if run_counter % active_refresh_every == 0 {
                 device.active_refresh_state()
             } else {
                 device.passive_refresh_state()
             } {
                 Ok(()) => (),
+                // the dongle is still present, only the headset is off
+                Err(DeviceError::HeadSetOff()) => {
+                    debug_println!("Headset is off");
+                }
                 Err(error) => {
                     eprintln!("{error}");
                     tray_handler.update(&device.device_properties());
+                    if let Some(eww_file) = &mut eww_file {
+                        eww_file.update(Some(&device.device_properties()));
+                    }
+                    *shared_properties.lock().unwrap() = Some(device.device_properties());
                     break; // try to reconnect
                 }
             };
@@ -250,17 +581,30 @@ fn main() {
                     }
                 }
             }
+            for button in button_receiver.try_iter() {
+                info_println!("Button pressed: {button:?}");
+                control_socket::broadcast_button(&button_subscribers, button);
+            }
 
             // with the default refresh_interval the state is only actively queried every 3min
             // querying the device to frequently can lead to instability
             let first = rx.recv_timeout(refresh_interval);
             for command in first.into_iter().chain(rx.try_iter()) {
                 let _ = device.try_apply(command);
-                std::thread::sleep(hyper_headset::devices::RESPONSE_DELAY);
+                std::thread::sleep(device.settle_delay());
                 let _ = device.active_refresh_state();
             }
+            if reconnect.swap(false, Ordering::Relaxed) {
+                debug_println!("Reconnecting");
+                break;
+            }
 
             tray_handler.update(&device.device_properties());
+            notifier.update(&device.device_properties());
+            if let Some(eww_file) = &mut eww_file {
+                eww_file.update(Some(&device.device_properties()));
+            }
+            *shared_properties.lock().unwrap() = Some(device.device_properties());
             run_counter += 1;
         }
     }
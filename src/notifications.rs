@@ -0,0 +1,192 @@
+use std::time::{Duration, Instant};
+
+use dbus::{arg::PropMap, blocking::Connection};
+use hyper_headset::{
+    config::low_battery_threshold,
+    devices::{ChargingStatus, DeviceProperties},
+};
+
+const DBUS_TIMEOUT: Duration = Duration::from_secs(1);
+/// Minimum time between two notifications of the same kind, so a flapping link does not spam
+const NOTIFICATION_COOLDOWN: Duration = Duration::from_secs(60);
+/// Shown if `notify_on` is not configured
+pub const DEFAULT_NOTIFICATIONS: [NotificationKind; 3] = [
+    NotificationKind::Charging,
+    NotificationKind::ChargeComplete,
+    NotificationKind::Microphone,
+];
+
+/// The transitions a notification can be shown for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    LowBattery,
+    ChargeComplete,
+    Charging,
+    Mute,
+    Connection,
+    Microphone,
+}
+
+impl NotificationKind {
+    /// Parses the names used in `notify_on`, e.g. "low_battery".
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low_battery" => Some(Self::LowBattery),
+            "charge_complete" => Some(Self::ChargeComplete),
+            "charging" => Some(Self::Charging),
+            "mute" => Some(Self::Mute),
+            "connection" => Some(Self::Connection),
+            "microphone" => Some(Self::Microphone),
+            _ => None,
+        }
+    }
+}
+
+/// Shows desktop notifications when the device properties change.
+/// Only the kinds given in `enabled` are shown.
+pub struct Notifier {
+    enabled: Vec<NotificationKind>,
+    previous: Option<DeviceProperties>,
+    last_sent: Vec<(NotificationKind, Instant)>,
+    connection: Option<Connection>,
+}
+
+impl Notifier {
+    pub fn new(enabled: Vec<NotificationKind>) -> Self {
+        Notifier {
+            enabled,
+            previous: None,
+            last_sent: Vec::new(),
+            connection: None,
+        }
+    }
+
+    /// Compares the properties with the previous ones and notifies about transitions.
+    pub fn update(&mut self, properties: &DeviceProperties) {
+        let Some(previous) = self.previous.replace(properties.clone()) else {
+            return;
+        };
+        for (kind, message) in transitions(&previous, properties) {
+            if !self.enabled.contains(&kind) {
+                continue;
+            }
+            let now = Instant::now();
+            self.last_sent
+                .retain(|(_, sent)| now.duration_since(*sent) < NOTIFICATION_COOLDOWN);
+            if self
+                .last_sent
+                .iter()
+                .any(|(sent_kind, _)| *sent_kind == kind)
+            {
+                continue;
+            }
+            self.last_sent.push((kind, now));
+            self.send(&message);
+        }
+    }
+
+    fn send(&mut self, body: &str) {
+        if self.connection.is_none() {
+            match Connection::new_session() {
+                Ok(connection) => self.connection = Some(connection),
+                Err(e) => {
+                    eprintln!("Failed to connect to the notification service: {e}");
+                    return;
+                }
+            }
+        }
+        let Some(connection) = &self.connection else {
+            return;
+        };
+        let proxy = connection.with_proxy(
+            "org.freedesktop.Notifications",
+            "/org/freedesktop/Notifications",
+            DBUS_TIMEOUT,
+        );
+        let result: Result<(u32,), _> = proxy.method_call(
+            "org.freedesktop.Notifications",
+            "Notify",
+            (
+                "HyperHeadset",
+                0u32,
+                "audio-headset",
+                "HyperHeadset",
+                body,
+                Vec::<String>::new(),
+                PropMap::new(),
+                -1i32,
+            ),
+        );
+        if let Err(e) = result {
+            eprintln!("Failed to show notification: {e}");
+        }
+    }
+}
+
+fn transitions(
+    previous: &DeviceProperties,
+    current: &DeviceProperties,
+) -> Vec<(NotificationKind, String)> {
+    let mut transitions = Vec::new();
+    if let (Some(previous_connected), Some(connected)) = (previous.connected, current.connected) {
+        if previous_connected != connected {
+            let message = if connected {
+                "Headset connected"
+            } else {
+                "Headset disconnected"
+            };
+            transitions.push((NotificationKind::Connection, message.to_string()));
+        }
+    }
+    if let (Some(previous_mic), Some(mic)) = (previous.mic_connected, current.mic_connected) {
+        if previous_mic != mic {
+            let message = if mic {
+                "Microphone attached"
+            } else {
+                "Microphone detached"
+            };
+            transitions.push((NotificationKind::Microphone, message.to_string()));
+        }
+    }
+    if let (Some(previous_muted), Some(muted)) = (previous.muted, current.muted) {
+        if previous_muted != muted {
+            let message = if muted {
+                "Microphone muted"
+            } else {
+                "Microphone unmuted"
+            };
+            transitions.push((NotificationKind::Mute, message.to_string()));
+        }
+    }
+    if let (Some(previous_charging), Some(charging)) = (previous.charging, current.charging) {
+        match (previous_charging, charging) {
+            (ChargingStatus::Charging, ChargingStatus::FullyCharged) => transitions.push((
+                NotificationKind::ChargeComplete,
+                "Headset fully charged".to_string(),
+            )),
+            (ChargingStatus::NotCharging, ChargingStatus::Charging) => transitions.push((
+                NotificationKind::Charging,
+                "Headset started charging".to_string(),
+            )),
+            (
+                ChargingStatus::Charging | ChargingStatus::FullyCharged,
+                ChargingStatus::NotCharging,
+            ) => transitions.push((
+                NotificationKind::Charging,
+                "Headset stopped charging".to_string(),
+            )),
+            _ => (),
+        }
+    }
+    let charging = matches!(current.charging, Some(ChargingStatus::Charging));
+    if let (Some(previous_level), Some(level)) = (previous.battery_level, current.battery_level) {
+        let threshold = low_battery_threshold();
+        if !charging && previous_level >= threshold && level < threshold {
+            transitions.push((
+                NotificationKind::LowBattery,
+                format!("Headset battery low: {level}%"),
+            ));
+        }
+    }
+    transitions
+}
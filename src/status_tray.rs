@@ -1,35 +1,62 @@
-use std::sync::mpsc::Sender;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+    Arc,
+};
 
-use hyper_headset::devices::{format_int_value, DeviceEvent, DeviceProperties, PropertyType};
+use hyper_headset::{
+    config::Profile,
+    devices::{DeviceEvent, DeviceProperties, PropertyType},
+    last_state::LastState,
+};
 use ksni::{
     menu::{StandardItem, SubMenu},
     Handle, MenuItem, ToolTip, Tray, TrayService,
 };
 
-use crate::tray_battery_icon_state::TrayBatteryIconState;
+use crate::tray_battery_icon_state::{PixmapTheme, TrayBatteryIconState};
 
 pub struct TrayHandler {
     handle: Handle<StatusTray>,
+    /// The properties last shown, used to skip updates that would not change anything
+    shown_properties: Option<DeviceProperties>,
+    last_state: Option<LastState>,
 }
 
 const NO_COMPATIBLE_DEVICE: &str = "No compatible device found.\nIs the dongle plugged in?\nIf you are using Linux did you\nadd the Udev rules?";
-const HEADSET_NOT_CONNECTED: &str = "Headset is not connected";
+const HEADSET_OFF: &str = "Headset is off";
+const MIC_DETACHED: &str = "Microphone detached";
 
 impl TrayHandler {
     pub fn new(tray: StatusTray) -> Self {
+        let last_state = tray.last_state.clone();
         let tray_service = TrayService::new(tray);
         let handle = tray_service.handle();
         tray_service.spawn();
-        TrayHandler { handle }
+        TrayHandler {
+            handle,
+            shown_properties: None,
+            last_state,
+        }
     }
 
-    pub fn update(&self, properties: &DeviceProperties) {
+    pub fn update(&mut self, properties: &DeviceProperties) {
+        LastState::remember(&mut self.last_state, properties);
+        if self.shown_properties.as_ref() == Some(properties) {
+            return;
+        }
+        self.shown_properties = Some(properties.clone());
+        let last_state = self.last_state.clone();
         self.handle.update(|tray| {
+            tray.last_state = last_state;
             tray.device_properties = Some(properties.clone());
         })
     }
 
-    pub fn clear_state(&self) {
+    pub fn clear_state(&mut self) {
+        if self.shown_properties.take().is_none() {
+            return;
+        }
         self.handle.update(|tray| {
             tray.device_properties = None;
         })
@@ -40,17 +67,72 @@ pub struct StatusTray {
     theme_name: Option<String>,
     device_properties: Option<DeviceProperties>,
     update_sender: Sender<DeviceEvent>,
+    reconnect: Arc<AtomicBool>,
     monochrome_icons: bool,
+    pixmap_theme: Option<PixmapTheme>,
+    instance_name: Option<String>,
+    last_state: Option<LastState>,
+    profiles: Vec<Profile>,
 }
 
 impl StatusTray {
-    pub fn new(update_sender: Sender<DeviceEvent>, monochrome_icons: bool) -> Self {
+    /// `battery_pixmap` is one of "auto", "light", or "dark".
+    /// If set, a rendered battery icon is used instead of the themed icons.
+    /// `reconnect` is set when the user asks to open the device again.
+    /// `profiles` are offered in a submenu that applies all settings of a profile.
+    pub fn new(
+        update_sender: Sender<DeviceEvent>,
+        reconnect: Arc<AtomicBool>,
+        monochrome_icons: bool,
+        battery_pixmap: Option<&str>,
+        instance_name: Option<String>,
+        profiles: Vec<Profile>,
+    ) -> Self {
         let theme_name = linicon::get_system_theme();
+        let pixmap_theme = battery_pixmap.map(|theme| match theme {
+            "light" => PixmapTheme::Light,
+            "dark" => PixmapTheme::Dark,
+            _ => PixmapTheme::from_theme_name(theme_name.as_ref()),
+        });
         StatusTray {
             theme_name,
             device_properties: None,
             update_sender,
+            reconnect,
             monochrome_icons,
+            pixmap_theme,
+            instance_name,
+            last_state: LastState::load(),
+            profiles,
+        }
+    }
+
+    /// Appends when the headset was last seen, if known.
+    fn with_last_seen(&self, message: &str) -> String {
+        match &self.last_state {
+            Some(last_state) => format!("{message}\n{}", last_state.describe()),
+            None => message.to_string(),
+        }
+    }
+
+    /// Appends the instance name, if any, to distinguish multiple running instances.
+    fn with_instance_name(&self, title: String) -> String {
+        match &self.instance_name {
+            Some(instance_name) => format!("{title} ({instance_name})"),
+            None => title,
+        }
+    }
+
+    fn battery_pixmap(&self) -> Option<ksni::Icon> {
+        TrayBatteryIconState::from_device_properties(self.device_properties.as_ref())
+            .linux_icon_pixmap(self.pixmap_theme?)
+    }
+
+    fn reconnect_icon(&self) -> &'static str {
+        if self.monochrome_icons {
+            "view-refresh-symbolic"
+        } else {
+            "view-refresh"
         }
     }
 
@@ -65,41 +147,60 @@ impl StatusTray {
 
 impl Tray for StatusTray {
     fn id(&self) -> String {
-        env!("CARGO_PKG_NAME").into()
+        match &self.instance_name {
+            Some(instance_name) => format!("{}-{instance_name}", env!("CARGO_PKG_NAME")),
+            None => env!("CARGO_PKG_NAME").into(),
+        }
     }
 
     fn icon_name(&self) -> String {
+        // hosts prefer the icon name over the pixmap
+        if self.battery_pixmap().is_some() {
+            return String::new();
+        }
         TrayBatteryIconState::from_device_properties(self.device_properties.as_ref())
             .linux_icon_name(self.monochrome_icons, self.theme_name.as_ref())
             .to_string()
     }
 
+    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+        self.battery_pixmap().into_iter().collect()
+    }
+
     fn tool_tip(&self) -> ToolTip {
         let Some(device_properties) = self.device_properties.as_ref() else {
             return ToolTip {
-                title: "Unknown".to_string(),
-                description: NO_COMPATIBLE_DEVICE.to_string(),
+                title: self.with_instance_name("Unknown".to_string()),
+                description: self.with_last_seen(NO_COMPATIBLE_DEVICE),
                 icon_name: TrayBatteryIconState::NoDevice
                     .linux_icon_name(self.monochrome_icons, self.theme_name.as_ref()),
                 icon_pixmap: Vec::new(),
             };
         };
         let description = if device_properties.connected.unwrap_or(false) {
-            device_properties
+            let properties = device_properties
                 .to_string_with_padding(0)
                 .lines()
                 .filter(|l| !l.contains("Unknown"))
                 .collect::<Vec<&str>>()
-                .join("\n")
+                .join("\n");
+            // a removed boom mic is easy to miss in the list, so it goes first
+            if device_properties.mic_connected == Some(false) {
+                format!("{MIC_DETACHED}\n{properties}")
+            } else {
+                properties
+            }
         } else {
-            HEADSET_NOT_CONNECTED.to_string()
+            self.with_last_seen(HEADSET_OFF)
         };
 
         ToolTip {
-            title: device_properties
-                .device_name
-                .clone()
-                .unwrap_or("Unknown".to_string()),
+            title: self.with_instance_name(
+                device_properties
+                    .device_name
+                    .clone()
+                    .unwrap_or("Unknown".to_string()),
+            ),
             description,
             icon_name: TrayBatteryIconState::from_device_properties(Some(device_properties))
                 .linux_icon_name(self.monochrome_icons, self.theme_name.as_ref())
@@ -116,12 +217,22 @@ impl Tray for StatusTray {
             activate: Box::new(|_| std::process::exit(0)),
             ..Default::default()
         };
+        let reconnect_icon = self.reconnect_icon();
+        let make_reconnect = || {
+            let reconnect = self.reconnect.clone();
+            StandardItem {
+                label: "Reconnect".into(),
+                icon_name: reconnect_icon.into(),
+                activate: Box::new(move |_| reconnect.store(true, Ordering::Relaxed)),
+                ..Default::default()
+            }
+        };
         let mut menu_items: Vec<MenuItem<Self>> = Vec::new();
 
         let Some(device_properties) = self.device_properties.as_ref() else {
             menu_items.push(
                 StandardItem {
-                    label: NO_COMPATIBLE_DEVICE.to_string(),
+                    label: self.with_last_seen(NO_COMPATIBLE_DEVICE),
                     enabled: false,
                     ..Default::default()
                 }
@@ -135,19 +246,22 @@ impl Tray for StatusTray {
         if !device_properties.connected.unwrap_or(false) {
             menu_items.push(
                 StandardItem {
-                    label: HEADSET_NOT_CONNECTED.to_string(),
+                    label: self.with_last_seen(HEADSET_OFF),
                     enabled: false,
                     ..Default::default()
                 }
                 .into(),
             );
             menu_items.push(MenuItem::Separator);
+            menu_items.push(make_reconnect().into());
             menu_items.push(make_exit().into());
             return menu_items;
         }
         for property in device_properties.get_properties() {
             match property {
-                hyper_headset::devices::PropertyDescriptorWrapper::Int(property, []) => {
+                hyper_headset::devices::PropertyDescriptorWrapper::Int(property, options)
+                    if options.values.is_empty() =>
+                {
                     let Some(current_value) = property.data else {
                         continue;
                     };
@@ -157,7 +271,7 @@ impl Tray for StatusTray {
                             label: format!(
                                 "{}: {}",
                                 property.pretty_name,
-                                format_int_value(current_value, property.suffix)
+                                options.format(current_value, property.suffix)
                             ),
                             enabled: false,
                             activate: Box::new(move |_| {
@@ -174,15 +288,16 @@ impl Tray for StatusTray {
                     };
                     let create_event = property.create_event;
                     let sub_menu = options
+                        .values
                         .iter()
-                        .map(|val| {
+                        .map(|&val| {
                             let update_sender = self.update_sender.clone();
                             StandardItem {
-                                label: format_int_value(*val, property.suffix),
+                                label: options.format(val, property.suffix),
                                 enabled: property.property_type == PropertyType::ReadWrite
                                     && property.data.is_some(),
                                 activate: Box::new(move |_| {
-                                    if let Some(command) = (create_event)(*val) {
+                                    if let Some(command) = (create_event)(val) {
                                         let _ = update_sender.send(command);
                                     }
                                 }),
@@ -196,7 +311,7 @@ impl Tray for StatusTray {
                             label: format!(
                                 "{}: {}",
                                 property.pretty_name,
-                                format_int_value(current_value, property.suffix)
+                                options.format(current_value, property.suffix)
                             ),
                             enabled: property.property_type == PropertyType::ReadWrite
                                 && property.data.is_some(),
@@ -253,7 +368,37 @@ impl Tray for StatusTray {
             }
         }
 
+        if !self.profiles.is_empty() {
+            let sub_menu = self
+                .profiles
+                .iter()
+                .map(|profile| {
+                    let update_sender = self.update_sender.clone();
+                    let settings = profile.settings.clone();
+                    StandardItem {
+                        label: profile.name.clone(),
+                        activate: Box::new(move |_| {
+                            for setting in &settings {
+                                let _ = update_sender.send(*setting);
+                            }
+                        }),
+                        ..Default::default()
+                    }
+                    .into()
+                })
+                .collect();
+            menu_items.push(
+                SubMenu {
+                    label: "Profile".into(),
+                    submenu: sub_menu,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
         menu_items.push(MenuItem::Separator);
+        menu_items.push(make_reconnect().into());
         menu_items.push(make_exit().into());
         menu_items
     }
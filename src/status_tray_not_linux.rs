@@ -3,7 +3,13 @@ use std::{
     sync::{mpsc::Sender, Arc, Mutex},
 };
 
-use hyper_headset::devices::{format_int_value, DeviceEvent, DeviceProperties, PropertyType};
+#[cfg(target_os = "windows")]
+use hyper_headset::devices::ChargingStatus;
+use hyper_headset::{
+    config::Profile,
+    devices::{DeviceEvent, DeviceProperties, PropertyType},
+    last_state::LastState,
+};
 #[cfg(target_os = "windows")]
 use image::{Rgba, RgbaImage};
 #[cfg(target_os = "windows")]
@@ -23,7 +29,7 @@ use winreg::{
 use crate::tray_battery_icon_state::{TrayBatteryIconState, WindowsIconKey};
 
 const NO_COMPATIBLE_DEVICE: &str = "No compatible device found. Is the dongle plugged in?";
-const HEADSET_NOT_CONNECTED: &str = "Headset is not connected";
+const HEADSET_OFF: &str = "Headset is off";
 #[cfg(target_os = "windows")]
 const RUN_KEY_PATH: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
 #[cfg(target_os = "windows")]
@@ -33,6 +39,10 @@ const STARTUP_APPROVED_RUN_KEY_PATH: &str =
 const STARTUP_VALUE_NAME: &str = "HyperHeadset";
 #[cfg(target_os = "windows")]
 const WINDOWS_ICON_SIZE: u32 = 16;
+/// Toasts need the id of a registered app, PowerShell is registered on every Windows install
+#[cfg(target_os = "windows")]
+const TOAST_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
 
 #[cfg(target_os = "windows")]
 fn create_default_tray_icon() -> tray_icon::Icon {
@@ -43,6 +53,22 @@ fn create_default_tray_icon() -> tray_icon::Icon {
     tray_icon::Icon::from_rgba(img.into_raw(), w, h).unwrap()
 }
 
+#[cfg(target_os = "windows")]
+fn show_toast(message: &str) -> windows::core::Result<()> {
+    use windows::{
+        core::HSTRING,
+        Data::Xml::Dom::XmlDocument,
+        UI::Notifications::{ToastNotification, ToastNotificationManager},
+    };
+
+    let content = XmlDocument::new()?;
+    content.LoadXml(&HSTRING::from(format!(
+        "<toast><visual><binding template=\"ToastGeneric\"><text>HyperHeadset</text><text>{message}</text></binding></visual></toast>"
+    )))?;
+    let toast = ToastNotification::CreateToastNotification(&content)?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(TOAST_APP_ID))?.Show(&toast)
+}
+
 #[cfg(target_os = "windows")]
 fn draw_rect(image: &mut RgbaImage, x: i32, y: i32, width: i32, height: i32, color: Rgba<u8>) {
     for px in x.max(0)..(x + width).min(WINDOWS_ICON_SIZE as i32) {
@@ -92,7 +118,7 @@ fn render_windows_battery_icon_rgba(key: WindowsIconKey) -> Vec<u8> {
     // Charging overrides battery-level color with yellow background.
     let background_color = if key.charging {
         Rgba([245, 216, 64, 255])
-    } else if key.percent < 30 {
+    } else if key.percent < hyper_headset::config::low_battery_threshold() {
         Rgba([220, 90, 90, 255])
     } else {
         Rgba([96, 196, 106, 255])
@@ -185,8 +211,10 @@ type CallbackMap = Arc<Mutex<HashMap<MenuId, Box<dyn Fn() + Send + Sync>>>>;
 pub struct TrayApp {
     pub tray_icon: Option<TrayIcon>,
     pub sender: Sender<DeviceEvent>,
+    profiles: Vec<Profile>,
     callbacks: CallbackMap,
     current_state: Option<Option<DeviceProperties>>,
+    last_state: Option<LastState>,
     #[cfg(target_os = "windows")]
     icon_cache: HashMap<WindowsIconKey, Vec<u8>>,
     #[cfg(target_os = "windows")]
@@ -250,7 +278,7 @@ impl ApplicationHandler<Option<DeviceProperties>> for TrayApp {
 }
 
 impl TrayApp {
-    pub fn new(sender: Sender<DeviceEvent>) -> Self {
+    pub fn new(sender: Sender<DeviceEvent>, profiles: Vec<Profile>) -> Self {
         let callbacks: CallbackMap = Arc::new(Mutex::new(HashMap::new()));
 
         let callbacks_clone = Arc::clone(&callbacks);
@@ -267,8 +295,10 @@ impl TrayApp {
         Self {
             tray_icon: None,
             sender,
+            profiles,
             callbacks,
             current_state: None,
+            last_state: LastState::load(),
             #[cfg(target_os = "windows")]
             icon_cache: HashMap::new(),
             #[cfg(target_os = "windows")]
@@ -276,6 +306,33 @@ impl TrayApp {
         }
     }
 
+    /// Shows a toast when the headset starts or stops charging or is fully charged
+    #[cfg(target_os = "windows")]
+    fn notify_charging(&self, device_properties: Option<&DeviceProperties>) {
+        let previous = self
+            .current_state
+            .as_ref()
+            .and_then(|state| state.as_ref())
+            .and_then(|properties| properties.charging);
+        let current = device_properties.and_then(|properties| properties.charging);
+        let message = match (previous, current) {
+            (Some(ChargingStatus::Charging), Some(ChargingStatus::FullyCharged)) => {
+                "Headset fully charged"
+            }
+            (Some(ChargingStatus::NotCharging), Some(ChargingStatus::Charging)) => {
+                "Headset started charging"
+            }
+            (
+                Some(ChargingStatus::Charging | ChargingStatus::FullyCharged),
+                Some(ChargingStatus::NotCharging),
+            ) => "Headset stopped charging",
+            _ => return,
+        };
+        if let Err(e) = show_toast(message) {
+            eprintln!("Failed to show notification: {e}");
+        }
+    }
+
     #[cfg(target_os = "windows")]
     fn update_windows_icon(&mut self, device_properties: Option<&DeviceProperties>) {
         let Some(tray) = self.tray_icon.as_ref() else {
@@ -311,6 +368,19 @@ impl TrayApp {
             }
         }
 
+        if let Some(device_properties) = device_properties.as_ref() {
+            LastState::remember(&mut self.last_state, device_properties);
+        }
+        // appends when the headset was last seen, if known
+        let with_last_seen = |message: &str| match &self.last_state {
+            Some(last_state) => format!("{message}\n{}", last_state.describe()),
+            None => message.to_string(),
+        };
+        let no_compatible_device = with_last_seen(NO_COMPATIBLE_DEVICE);
+        let headset_off = with_last_seen(HEADSET_OFF);
+
+        #[cfg(target_os = "windows")]
+        self.notify_charging(device_properties.as_ref());
         #[cfg(target_os = "windows")]
         self.update_windows_icon(device_properties.as_ref());
 
@@ -325,10 +395,10 @@ impl TrayApp {
         let mut new_callbacks: HashMap<MenuId, Box<dyn Fn() + Send + Sync>> = HashMap::new();
 
         let Some(device_properties) = device_properties else {
-            let _ = tray.set_tooltip(Some(NO_COMPATIBLE_DEVICE));
+            let _ = tray.set_tooltip(Some(&no_compatible_device));
             #[cfg(target_os = "macos")]
             tray.set_title(Some(&format!("🎧?")));
-            let status_item = MenuItem::new(NO_COMPATIBLE_DEVICE, false, None);
+            let status_item = MenuItem::new(&no_compatible_device, false, None);
             menu.append(&status_item).unwrap();
             menu.append(&PredefinedMenuItem::separator()).unwrap();
 
@@ -350,10 +420,10 @@ impl TrayApp {
         };
 
         if !device_properties.connected.unwrap_or(false) {
-            let _ = tray.set_tooltip(Some(HEADSET_NOT_CONNECTED));
+            let _ = tray.set_tooltip(Some(&headset_off));
             #[cfg(target_os = "macos")]
             tray.set_title(Some(&format!("🎧?")));
-            let status_item = MenuItem::new(HEADSET_NOT_CONNECTED, false, None);
+            let status_item = MenuItem::new(&headset_off, false, None);
             menu.append(&status_item).unwrap();
             menu.append(&PredefinedMenuItem::separator()).unwrap();
 
@@ -402,7 +472,9 @@ impl TrayApp {
 
         for property in device_properties.get_properties() {
             match property {
-                hyper_headset::devices::PropertyDescriptorWrapper::Int(property, []) => {
+                hyper_headset::devices::PropertyDescriptorWrapper::Int(property, options)
+                    if options.values.is_empty() =>
+                {
                     let Some(current_value) = property.data else {
                         continue;
                     };
@@ -410,14 +482,14 @@ impl TrayApp {
                         format!(
                             "{}: {}",
                             property.pretty_name,
-                            format_int_value(current_value, property.suffix)
+                            options.format(current_value, property.suffix)
                         ),
                         false,
                         None,
                     );
                     let _ = menu.append(&menu_item);
                 }
-                hyper_headset::devices::PropertyDescriptorWrapper::Int(property, items) => {
+                hyper_headset::devices::PropertyDescriptorWrapper::Int(property, options) => {
                     let Some(current_value) = property.data else {
                         continue;
                     };
@@ -425,17 +497,14 @@ impl TrayApp {
                         format!(
                             "{}: {}",
                             property.pretty_name,
-                            format_int_value(current_value, property.suffix),
+                            options.format(current_value, property.suffix),
                         ),
                         property.property_type == PropertyType::ReadWrite,
                     );
 
-                    for item_value in items {
-                        let entry = MenuItem::new(
-                            format_int_value(*item_value, property.suffix),
-                            true,
-                            None,
-                        );
+                    for &item_value in &options.values {
+                        let entry =
+                            MenuItem::new(options.format(item_value, property.suffix), true, None);
                         submenu.append(&entry).unwrap();
 
                         let create_event = property.create_event;
@@ -444,7 +513,7 @@ impl TrayApp {
                         new_callbacks.insert(
                             entry_id,
                             Box::new(move || {
-                                if let Some(event) = (create_event)(*item_value) {
+                                if let Some(event) = (create_event)(item_value) {
                                     let _ = tx.send(event);
                                 }
                             }),
@@ -496,6 +565,26 @@ impl TrayApp {
             }
         }
 
+        if !self.profiles.is_empty() {
+            let submenu = Submenu::new("Profile", true);
+            for profile in &self.profiles {
+                let entry = MenuItem::new(&profile.name, true, None);
+                submenu.append(&entry).unwrap();
+
+                let settings = profile.settings.clone();
+                let tx = self.sender.clone();
+                new_callbacks.insert(
+                    entry.id().clone(),
+                    Box::new(move || {
+                        for setting in &settings {
+                            let _ = tx.send(*setting);
+                        }
+                    }),
+                );
+            }
+            menu.append(&submenu).unwrap();
+        }
+
         menu.append(&PredefinedMenuItem::separator()).unwrap();
 
         #[cfg(target_os = "windows")]
@@ -1,5 +1,8 @@
 use hyper_headset::devices::{ChargingStatus, DeviceProperties};
 
+#[cfg(target_os = "linux")]
+use hyper_headset::config::low_battery_threshold;
+
 #[cfg(target_os = "linux")]
 use freedesktop_icons::lookup;
 
@@ -9,6 +12,63 @@ const HEADSET_MONOCHROME: &str = "audio-headset-symbolic";
 const HEADSET: &str = "audio-headset";
 #[cfg(target_os = "linux")]
 const HEADSET_FALLBACK: &str = "headset";
+#[cfg(target_os = "linux")]
+const LINUX_ICON_SIZE: i32 = 32;
+
+/// Color scheme of the rendered tray battery icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg(target_os = "linux")]
+pub enum PixmapTheme {
+    Light,
+    Dark,
+}
+
+#[cfg(target_os = "linux")]
+impl PixmapTheme {
+    /// Guesses the color scheme from the name of the icon theme.
+    pub fn from_theme_name(theme_name: Option<&String>) -> Self {
+        match theme_name {
+            Some(name) if name.to_lowercase().contains("dark") => Self::Dark,
+            _ => Self::Light,
+        }
+    }
+
+    /// Outline color in ARGB
+    fn outline_color(self) -> [u8; 4] {
+        match self {
+            // dark panels need a bright outline and vice versa
+            Self::Dark => [255, 230, 230, 230],
+            Self::Light => [255, 40, 40, 40],
+        }
+    }
+}
+
+/// Fills a rectangle of an ARGB32 image with the given color.
+#[cfg(target_os = "linux")]
+fn fill_rect(data: &mut [u8], x: i32, y: i32, width: i32, height: i32, color: [u8; 4]) {
+    for px in x.max(0)..(x + width).min(LINUX_ICON_SIZE) {
+        for py in y.max(0)..(y + height).min(LINUX_ICON_SIZE) {
+            let offset = ((py * LINUX_ICON_SIZE + px) * 4) as usize;
+            data[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+/// Charging bolt drawn over the battery, `#` marks a pixel
+#[cfg(target_os = "linux")]
+#[rustfmt::skip]
+const BOLT: [&str; 10] = [
+    "....###.",
+    "...###..",
+    "..###...",
+    ".#######",
+    "#######.",
+    "...###..",
+    "..###...",
+    ".###....",
+    ".##.....",
+    ".#......",
+];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TrayBatteryIconState {
@@ -54,6 +114,53 @@ impl TrayBatteryIconState {
         }
     }
 
+    /// Renders a battery glyph filled according to the battery level, with a bolt while charging.
+    /// Returns None if there is no battery level to show.
+    #[cfg(target_os = "linux")]
+    pub fn linux_icon_pixmap(self, theme: PixmapTheme) -> Option<ksni::Icon> {
+        let Self::Connected { percent, charging } = self else {
+            return None;
+        };
+        let mut data = vec![0u8; (LINUX_ICON_SIZE * LINUX_ICON_SIZE * 4) as usize];
+        let outline = theme.outline_color();
+
+        // body outline (2px)
+        fill_rect(&mut data, 1, 8, 27, 2, outline);
+        fill_rect(&mut data, 1, 22, 27, 2, outline);
+        fill_rect(&mut data, 1, 8, 2, 16, outline);
+        fill_rect(&mut data, 26, 8, 2, 16, outline);
+        // terminal
+        fill_rect(&mut data, 28, 12, 3, 8, outline);
+
+        // same colors as the Windows icon
+        let fill_color = if charging {
+            [255, 245, 216, 64]
+        } else if percent < low_battery_threshold() {
+            [255, 220, 90, 90]
+        } else {
+            [255, 96, 196, 106]
+        };
+        // the inner area is 21px wide, always show at least a sliver
+        let fill_width = ((percent as i32 * 21) / 100).max(1);
+        fill_rect(&mut data, 4, 11, fill_width, 10, fill_color);
+        if charging {
+            // centered in the inner area
+            for (y, row) in BOLT.iter().enumerate() {
+                for (x, pixel) in row.chars().enumerate() {
+                    if pixel == '#' {
+                        fill_rect(&mut data, 11 + x as i32, 11 + y as i32, 1, 1, outline);
+                    }
+                }
+            }
+        }
+
+        Some(ksni::Icon {
+            width: LINUX_ICON_SIZE,
+            height: LINUX_ICON_SIZE,
+            data,
+        })
+    }
+
     #[cfg(target_os = "linux")]
     pub fn linux_icon_name(self, monochrome: bool, theme_name: Option<&String>) -> String {
         let if_icon_exists = |name: &str, fallback: &dyn Fn() -> String| {